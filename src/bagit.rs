@@ -0,0 +1,90 @@
+//! Validation of BagIt (RFC 8493) bags.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::compare::{collect_regular_files, verify_files, Expected, HashAlgo};
+use crate::report::{Category, DiffEntry, SubdirReport};
+
+/// Manifests we can check; other algorithms are not supported.
+pub const PAYLOAD_MANIFEST: &str = "manifest-sha256.txt";
+const TAG_MANIFEST: &str = "tagmanifest-sha256.txt";
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Undo the percent-encoding BagIt applies to CR, LF and `%` in file paths.
+fn decode_path(path: &str) -> String {
+    path.replace("%0D", "\r").replace("%0d", "\r").replace("%0A", "\n").replace("%0a", "\n").replace("%25", "%")
+}
+
+/// Parse a `<checksum> <path>` BagIt manifest.
+fn parse_manifest(name: &str, text: &str) -> io::Result<BTreeMap<PathBuf, Expected>> {
+    let mut files = BTreeMap::new();
+    for (lineno, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (digest, path) = line
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .ok_or_else(|| invalid(format!("{name} line {}: missing file path", lineno + 1)))?;
+        let rel = PathBuf::from(decode_path(path.trim_start()));
+        if rel.components().any(|c| !matches!(c, Component::Normal(_))) {
+            return Err(invalid(format!("{name} line {}: path escapes the bag: {}", lineno + 1, rel.display())));
+        }
+        files.insert(rel, Expected { size: None, digest: Some(digest.to_ascii_lowercase()), ..Expected::default() });
+    }
+    Ok(files)
+}
+
+/// Value of the `Payload-Oxum` (`<octets>.<count>`) field in `bag-info.txt`.
+fn payload_oxum(bag: &Path) -> Option<(u64, usize)> {
+    let info = fs::read_to_string(bag.join("bag-info.txt")).ok()?;
+    let value = info.lines().find_map(|l| l.strip_prefix("Payload-Oxum:"))?.trim();
+    let (octets, count) = value.split_once('.')?;
+    Some((octets.parse().ok()?, count.parse().ok()?))
+}
+
+/// Validate a bag's payload for completeness and fixity against its SHA-256
+/// manifest, and its tag files against the tag manifest if there is one.
+///
+/// Paths are reported relative to the bag root (`data/...`); a mismatching
+/// `Payload-Oxum` is reported as an error on `bag-info.txt`.
+pub fn validate(bag: &Path) -> io::Result<SubdirReport> {
+    if !bag.join("bagit.txt").is_file() {
+        return Err(invalid(format!("{} is not a BagIt bag (no bagit.txt)", bag.display())));
+    }
+    let manifest_path = bag.join(PAYLOAD_MANIFEST);
+    if !manifest_path.is_file() {
+        return Err(invalid(format!("{} has no {PAYLOAD_MANIFEST}; only SHA-256 bags are supported", bag.display())));
+    }
+
+    let mut expected = parse_manifest(PAYLOAD_MANIFEST, &fs::read_to_string(&manifest_path)?)?;
+    if let Some(path) = expected.keys().find(|p| !p.starts_with("data")) {
+        return Err(invalid(format!("{PAYLOAD_MANIFEST} lists a file outside data/: {}", path.display())));
+    }
+    if let Ok(text) = fs::read_to_string(bag.join(TAG_MANIFEST)) {
+        expected.extend(parse_manifest(TAG_MANIFEST, &text)?);
+    }
+
+    // Only the payload must be listed completely; unlisted tag files are fine.
+    let mut result = verify_files(bag, &expected, &[HashAlgo::Sha256], None);
+    result.entries.retain(|e| e.category != Category::MissingInA || e.path.starts_with("data"));
+
+    if let Some((octets, count)) = payload_oxum(bag) {
+        let payload: Vec<_> = collect_regular_files(&bag.join("data")).into_iter().collect();
+        let actual_octets: u64 = payload.iter().filter_map(|rel| fs::metadata(bag.join("data").join(rel)).ok()).map(|m| m.len()).sum();
+        if (actual_octets, payload.len()) != (octets, count) {
+            result.entries.push(DiffEntry {
+                error: Some(format!("Payload-Oxum is {octets}.{count} but payload has {actual_octets}.{}", payload.len())),
+                ..DiffEntry::new(Category::Errored, PathBuf::from("bag-info.txt"))
+            });
+        }
+    }
+
+    result.entries.sort_by_key(|e| e.category);
+    Ok(result)
+}
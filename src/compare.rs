@@ -0,0 +1,1493 @@
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+
+use clap::ValueEnum;
+use regex::bytes::RegexSet;
+use serde::Serialize;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::filetype::is_binary;
+use crate::filter::{device_id, Filter, Ignores};
+use crate::metadata::MetadataChecks;
+use crate::progress::Progress;
+use crate::report::{rfc3339, AttrDiff, Category, DiffEntry, MtimeMatch, Stats, SubdirReport, SubdirStatus};
+
+/// Files (symlinks and special files included) and directories found below
+/// a root, relative to it.
+#[derive(Debug, Default)]
+pub struct Walk {
+    pub files: HashSet<PathBuf>,
+    /// Directories other than the root itself.
+    pub dirs: HashSet<PathBuf>,
+}
+
+/// Identifies a file or directory across the different paths (symlinks,
+/// hardlinks) leading to it.
+#[cfg(unix)]
+type FileId = (u64, u64);
+#[cfg(not(unix))]
+type FileId = PathBuf;
+
+#[cfg(unix)]
+fn file_id(_path: &Path, meta: &fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(path: &Path, _meta: &fs::Metadata) -> Option<FileId> {
+    fs::canonicalize(path).ok()
+}
+
+/// Number of hardlinks to a file (always 1 where they cannot be counted).
+#[cfg(unix)]
+fn links(meta: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.nlink()
+}
+
+#[cfg(not(unix))]
+fn links(_meta: &fs::Metadata) -> u64 {
+    1
+}
+
+/// Whether `a` and `b` are hardlinks to the same file.
+fn same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    let (ma, mb) = (fs::metadata(a)?, fs::metadata(b)?);
+    Ok(links(&ma) > 1 && links(&mb) > 1 && file_id(a, &ma).is_some() && file_id(a, &ma) == file_id(b, &mb))
+}
+
+/// Digests of hardlinked files by file, so that each is read only once
+/// however many paths lead to it.
+#[derive(Debug, Default)]
+pub struct DigestCache(HashMap<FileId, Vec<u8>>);
+
+impl DigestCache {
+    /// Digest of the file at `path` and the number of bytes read for it.
+    pub fn digest(&mut self, path: &Path, algo: HashAlgo) -> io::Result<(Vec<u8>, u64)> {
+        let meta = fs::metadata(path)?;
+        let id = if links(&meta) > 1 { file_id(path, &meta) } else { None };
+        if let Some(digest) = id.as_ref().and_then(|id| self.0.get(id)) {
+            return Ok((digest.clone(), 0));
+        }
+        let digest = hash_file(path, algo)?;
+        if let Some(id) = id {
+            self.0.insert(id, digest.clone());
+        }
+        Ok((digest, meta.len()))
+    }
+}
+
+/// Recursively walks `root`. Symlinks are collected as files, unless
+/// `follow_symlinks` is set and they point to a directory, which is then
+/// walked like any other; a link back to one of its own ancestors is skipped.
+/// Only the entries accepted by `filter` (and not ignored by its gitignore
+/// rules) are collected.
+pub fn walk(root: &Path, follow_symlinks: bool, filter: &Filter) -> Walk {
+    // Each entry to visit comes with the ids of its ancestors and the ignore
+    // rules in effect in its directory.
+    let mut stack: Vec<(PathBuf, Vec<FileId>, Ignores)> = vec![(root.to_path_buf(), Vec::new(), Ignores::at_root(root, filter))];
+    let mut walk = Walk::default();
+    let device = filter.tree_device(root);
+
+    while let Some((current, ancestors, ignores)) = stack.pop() {
+        // The root itself may be given as a symlink to a directory.
+        let meta = if current == root { fs::metadata(&current) } else { fs::symlink_metadata(&current) };
+        let Ok(mut meta) = meta else {
+            continue;
+        };
+        if follow_symlinks && meta.is_symlink() {
+            meta = fs::metadata(&current).unwrap_or(meta);
+        }
+        if current != root && (ignores.ignored(&current, meta.is_dir()) || filter.hides(&current, &meta)) {
+            debug!(path = %current.display(), "ignored");
+            continue;
+        }
+        let relative = current.strip_prefix(root).ok().filter(|rel| filter.accepts(rel)).map(Path::to_path_buf);
+        if meta.is_dir() {
+            let mounted = device.is_some_and(|dev| device_id(&meta) != Some(dev));
+            if current.strip_prefix(root).is_ok_and(|rel| current != root && (mounted || !filter.descends(rel))) {
+                // Directories beyond the maximum depth and mount points are
+                // still collected.
+                debug!(dir = %current.display(), mounted, "not descending");
+                walk.dirs.extend(relative);
+                continue;
+            }
+            let mut ancestors = ancestors;
+            if follow_symlinks {
+                let Some(id) = file_id(&current, &meta) else {
+                    continue;
+                };
+                if ancestors.contains(&id) {
+                    warn!(dir = %current.display(), "symlink loop, not followed");
+                    continue;
+                }
+                ancestors.push(id);
+            }
+            debug!(dir = %current.display(), "walking");
+            if current != root {
+                walk.dirs.extend(relative);
+            }
+            let ignores = ignores.enter(&current);
+            if let Ok(entries) = fs::read_dir(&current) {
+                for entry in entries.flatten() {
+                    stack.push((entry.path(), ancestors.clone(), ignores.clone()));
+                }
+            }
+        } else {
+            // Regular files, symlinks and special files alike.
+            if filter.accepts_file(&current, &meta) {
+                walk.files.extend(relative);
+            }
+        }
+    }
+
+    walk
+}
+
+/// Recursively collects **file**, symlink and special file paths (relative
+/// to `root`) into a `HashSet`. Symlinks are not followed.
+pub fn collect_files(root: &Path) -> HashSet<PathBuf> {
+    walk(root, false, &Filter::default()).files
+}
+
+/// Like [`collect_files`], but only the paths that resolve to regular files,
+/// i.e. whose contents can be hashed.
+pub fn collect_regular_files(root: &Path) -> HashSet<PathBuf> {
+    collect_files(root).into_iter().filter(|f| root.join(f).is_file()).collect()
+}
+
+/// Whether `path` is a directory to walk: a symlink to one only counts with
+/// `follow_symlinks`.
+fn is_subdir(path: &Path, follow_symlinks: bool) -> bool {
+    if follow_symlinks { path.is_dir() } else { fs::symlink_metadata(path).is_ok_and(|m| m.is_dir()) }
+}
+
+/// Returns the set of **direct** subdirectories (relative to `root`),
+/// symlinks to directories included: [`compare_subdir`] compares those as
+/// links unless they are followed.
+pub fn direct_subdirs(root: &Path) -> HashSet<PathBuf> {
+    let mut dirs = HashSet::new();
+    if let Ok(entries) = fs::read_dir(root) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if let Ok(rel) = path.strip_prefix(root) {
+                    dirs.insert(rel.to_path_buf());
+                }
+            }
+        }
+    }
+    dirs
+}
+
+/// Digest algorithm used to compare file contents.
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgo {
+    /// SHA-256, compatible with `sha256sum` manifests
+    #[default]
+    Sha256,
+    /// BLAKE3, several times faster than SHA-256 on modern CPUs
+    Blake3,
+    /// 64-bit XXH3; not collision resistant, but limited only by disk speed
+    Xxh3,
+    /// CRC-32, for CPU-constrained machines; catches corruption, not tampering
+    Crc32,
+    /// MD5, for `md5sum` manifests; broken for security purposes
+    Md5,
+    /// SHA-1, for `sha1sum` manifests; broken for security purposes
+    Sha1,
+}
+
+impl HashAlgo {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashAlgo::Sha256 => "sha256",
+            HashAlgo::Blake3 => "blake3",
+            HashAlgo::Xxh3 => "xxh3",
+            HashAlgo::Crc32 => "crc32",
+            HashAlgo::Md5 => "md5",
+            HashAlgo::Sha1 => "sha1",
+        }
+    }
+
+    /// Name used in BSD-style tagged manifest lines (`TAG (path) = digest`).
+    pub fn tag(self) -> &'static str {
+        match self {
+            HashAlgo::Sha256 => "SHA256",
+            HashAlgo::Blake3 => "BLAKE3",
+            HashAlgo::Xxh3 => "XXH3",
+            HashAlgo::Crc32 => "CRC32",
+            HashAlgo::Md5 => "MD5",
+            HashAlgo::Sha1 => "SHA1",
+        }
+    }
+
+    /// Length of a digest in hexadecimal digits.
+    pub fn hex_len(self) -> usize {
+        match self {
+            HashAlgo::Sha256 | HashAlgo::Blake3 => 64,
+            HashAlgo::Xxh3 => 16,
+            HashAlgo::Crc32 => 8,
+            HashAlgo::Md5 => 32,
+            HashAlgo::Sha1 => 40,
+        }
+    }
+
+    /// Caveat to print alongside results that rely on a weak checksum.
+    pub fn collision_warning(self) -> Option<&'static str> {
+        match self {
+            HashAlgo::Crc32 => Some(
+                "crc32 checksums are 32 bits: about 1 in 4 billion differing files goes unnoticed, \
+                 and a deliberate collision is trivial to craft",
+            ),
+            _ => None,
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            HashAlgo::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgo::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgo::Xxh3 => Hasher::Xxh3(Box::new(Xxh3::new())),
+            HashAlgo::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            HashAlgo::Md5 => Hasher::Md5(Md5::new()),
+            HashAlgo::Sha1 => Hasher::Sha1(Sha1::new()),
+        }
+    }
+}
+
+/// In-progress digest of one of the [`HashAlgo`]s.
+enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<Xxh3>),
+    Crc32(crc32fast::Hasher),
+    Md5(Md5),
+    Sha1(Sha1),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+            Hasher::Xxh3(h) => h.update(data),
+            Hasher::Crc32(h) => h.update(data),
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Sha1(h) => h.update(data),
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+            Hasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+            // Big-endian, as printed by `xxhsum`.
+            Hasher::Xxh3(h) => h.digest().to_be_bytes().to_vec(),
+            Hasher::Crc32(h) => h.finalize().to_be_bytes().to_vec(),
+            Hasher::Md5(h) => h.finalize().to_vec(),
+            Hasher::Sha1(h) => h.finalize().to_vec(),
+        }
+    }
+}
+
+/// Stream a file and return its digest.
+pub fn hash_file(path: &Path, algo: HashAlgo) -> io::Result<Vec<u8>> {
+    Ok(hash_file_multi(path, &[algo])?.remove(0))
+}
+
+/// Stream a file once and return its digest in each of `algos`, in order.
+pub fn hash_file_multi(path: &Path, algos: &[HashAlgo]) -> io::Result<Vec<Vec<u8>>> {
+    debug!(file = %path.display(), algos = ?algos, "hashing");
+    let mut file = fs::File::open(path)?;
+    let mut hashers: Vec<Hasher> = algos.iter().map(|a| a.hasher()).collect();
+    // Large enough for BLAKE3 to use its SIMD code paths.
+    let mut buf = vec![0u8; 64 * 1024];
+
+    let Some(regions) = data_regions(&file) else {
+        // Looking for data regions may have moved the offset before giving up.
+        file.seek(SeekFrom::Start(0))?;
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 { break; }
+            for hasher in &mut hashers {
+                hasher.update(&buf[..n]);
+            }
+        }
+        return Ok(hashers.into_iter().map(Hasher::finalize).collect());
+    };
+
+    // Sparse file: holes are hashed as the zeros they read as, without
+    // reading them.
+    let zeros = vec![0u8; buf.len()];
+    let mut pos = 0;
+    for (start, end) in regions {
+        while pos < start {
+            let n = (start - pos).min(zeros.len() as u64) as usize;
+            for hasher in &mut hashers {
+                hasher.update(&zeros[..n]);
+            }
+            pos += n as u64;
+        }
+        file.seek(SeekFrom::Start(start))?;
+        let mut region = (&mut file).take(end - start);
+        loop {
+            let n = region.read(&mut buf)?;
+            if n == 0 { break; }
+            for hasher in &mut hashers {
+                hasher.update(&buf[..n]);
+            }
+            pos += n as u64;
+        }
+    }
+
+    Ok(hashers.into_iter().map(Hasher::finalize).collect())
+}
+
+/// The data regions `(start, end)` of a sparse file, ending with an empty
+/// region at its end so that trailing holes are accounted for; `None` when
+/// it has no holes or the platform cannot tell.
+#[cfg(target_os = "linux")]
+fn data_regions(file: &fs::File) -> Option<Vec<(u64, u64)>> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = file.metadata().ok()?;
+    let len = meta.len();
+    if meta.blocks() * 512 >= len {
+        return None;
+    }
+    let fd = file.as_raw_fd();
+    let mut regions = Vec::new();
+    let mut pos = 0;
+    while pos < len {
+        // SAFETY: lseek only moves the offset of a descriptor that stays open
+        // for the duration of the call.
+        let data = unsafe { libc::lseek(fd, pos as libc::off_t, libc::SEEK_DATA) };
+        if data < 0 {
+            // ENXIO: nothing but a hole up to the end of the file.
+            if io::Error::last_os_error().raw_os_error() == Some(libc::ENXIO) {
+                break;
+            }
+            return None;
+        }
+        // SAFETY: as above.
+        let hole = unsafe { libc::lseek(fd, data, libc::SEEK_HOLE) };
+        if hole < 0 {
+            return None;
+        }
+        regions.push((data as u64, hole as u64));
+        pos = hole as u64;
+    }
+    regions.push((len, len));
+    Some(regions)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn data_regions(_file: &fs::File) -> Option<Vec<(u64, u64)>> {
+    None
+}
+
+/// Lowercase hexadecimal representation of a digest.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Digests of the A and B side of a file pair.
+pub type DigestPair = (Vec<u8>, Vec<u8>);
+
+/// Returns `Ok(true)` if file contents differ. Uses size check first, then `algo`.
+/// The digests are returned too when both files had to be hashed, with the
+/// number of bytes read; hardlinked files are hashed once through `cache`.
+pub fn contents_differ(a: &Path, b: &Path, algo: HashAlgo, cache: &mut DigestCache) -> io::Result<(bool, Option<DigestPair>, u64)> {
+    let ma = fs::metadata(a)?;
+    let mb = fs::metadata(b)?;
+    if ma.len() != mb.len() {
+        return Ok((true, None, 0));
+    }
+    let ((da, ra), (db, rb)) = (cache.digest(a, algo)?, cache.digest(b, algo)?);
+    Ok((da != db, Some((da, db)), ra + rb))
+}
+
+/// Returns `Ok(true)` if the text files `a` and `b` differ once the lines
+/// matching `ignore` are left out, like `diff -I`, along with the number of
+/// bytes read. Files with NUL bytes are binary and differ as they are.
+pub fn lines_differ(a: &Path, b: &Path, ignore: &RegexSet) -> io::Result<(bool, u64)> {
+    let (x, y) = (fs::read(a)?, fs::read(b)?);
+    let read = (x.len() + y.len()) as u64;
+    if x.contains(&0) || y.contains(&0) {
+        return Ok((x != y, read));
+    }
+    let kept = |text: &[u8]| -> Vec<Vec<u8>> { text.split(|&c| c == b'\n').filter(|line| !ignore.is_match(line)).map(<[u8]>::to_vec).collect() };
+    Ok((kept(&x) != kept(&y), read))
+}
+
+/// How the contents of files present on both sides are compared.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContentCheck {
+    /// Hash both files fully with the chosen [`HashAlgo`].
+    #[default]
+    Hash,
+    /// Read both files in step and stop at the first differing byte.
+    Bytes,
+    /// Hash only the first, middle and last part of each file.
+    Quick,
+    /// Compare sizes only, without reading any contents.
+    SizeOnly,
+}
+
+/// Fill `buf` from `file` unless the end of the file comes first; returns
+/// the number of bytes read.
+fn read_full(file: &mut fs::File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Returns `Ok(true)` if file contents differ, comparing them byte by byte
+/// after a size check, along with the number of bytes read from both sides.
+pub fn bytes_differ(a: &Path, b: &Path) -> io::Result<(bool, u64)> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok((true, 0));
+    }
+    debug!(a = %a.display(), b = %b.display(), "comparing bytes");
+    let (mut fa, mut fb) = (fs::File::open(a)?, fs::File::open(b)?);
+    let (mut buf_a, mut buf_b) = (vec![0u8; 64 * 1024], vec![0u8; 64 * 1024]);
+    let mut read = 0;
+    loop {
+        let (na, nb) = (read_full(&mut fa, &mut buf_a)?, read_full(&mut fb, &mut buf_b)?);
+        read += (na + nb) as u64;
+        if buf_a[..na] != buf_b[..nb] {
+            return Ok((true, read));
+        }
+        if na == 0 {
+            return Ok((false, read));
+        }
+    }
+}
+
+/// Digest of the first, middle and last `sample` bytes of a file (all of
+/// it when it is not larger than the three samples), and the bytes read.
+pub fn hash_sampled(path: &Path, algo: HashAlgo, sample: u64) -> io::Result<(Vec<u8>, u64)> {
+    let len = fs::metadata(path)?.len();
+    if sample.checked_mul(3).is_none_or(|samples| len <= samples) {
+        return Ok((hash_file(path, algo)?, len));
+    }
+    debug!(file = %path.display(), sample, "hashing samples");
+    let mut file = fs::File::open(path)?;
+    let mut hasher = algo.hasher();
+    let mut buf = vec![0u8; sample as usize];
+    for offset in [0, len / 2 - sample / 2, len - sample] {
+        file.seek(SeekFrom::Start(offset))?;
+        let n = read_full(&mut file, &mut buf)?;
+        hasher.update(&buf[..n]);
+    }
+    Ok((hasher.finalize(), 3 * sample))
+}
+
+/// Size of the file (or symlink) at `path`, or `None` if it cannot be stat'ed.
+fn file_size(path: &Path) -> Option<u64> {
+    fs::symlink_metadata(path).ok().map(|m| m.len())
+}
+
+/// Modification time of the file (or symlink) at `path`, if available.
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    fs::symlink_metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The `n` most recently modified of `files` below `root`.
+fn newest(root: &Path, files: &HashSet<PathBuf>, n: usize) -> HashSet<PathBuf> {
+    let mut by_mtime: Vec<_> = files.iter().map(|f| (file_mtime(&root.join(f)), f)).collect();
+    by_mtime.sort_by(|x, y| y.cmp(x));
+    by_mtime.into_iter().take(n).map(|(_, f)| f.clone()).collect()
+}
+
+/// Only the `files` in `selected`, and the `dirs` holding them.
+fn keep_files(files: HashSet<PathBuf>, dirs: HashSet<PathBuf>, selected: &HashSet<PathBuf>) -> (HashSet<PathBuf>, HashSet<PathBuf>) {
+    let files: HashSet<_> = files.into_iter().filter(|f| selected.contains(f)).collect();
+    let holding: HashSet<&Path> = files.iter().flat_map(|f| f.ancestors().skip(1)).collect();
+    let dirs = dirs.into_iter().filter(|d| holding.contains(d.as_path())).collect();
+    (files, dirs)
+}
+
+/// Whether `path` is a symlink whose target does not exist.
+fn is_dangling(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.is_symlink()) && fs::metadata(path).is_err()
+}
+
+/// Kind of the entry at `path`, not following symlinks: `file`, `dir`,
+/// `symlink`, `fifo`, `socket`, `block-device` or `char-device`.
+fn file_kind(path: &Path) -> &'static str {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return "unknown";
+    };
+    let file_type = meta.file_type();
+    if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_file() {
+        "file"
+    } else if file_type.is_dir() {
+        "dir"
+    } else {
+        special_kind(file_type)
+    }
+}
+
+#[cfg(unix)]
+fn special_kind(file_type: fs::FileType) -> &'static str {
+    use std::os::unix::fs::FileTypeExt;
+    if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "block-device"
+    } else if file_type.is_char_device() {
+        "char-device"
+    } else {
+        "special"
+    }
+}
+
+#[cfg(not(unix))]
+fn special_kind(_file_type: fs::FileType) -> &'static str {
+    "special"
+}
+
+/// `major:minor` of the device node at `path`.
+#[cfg(unix)]
+fn device(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let rdev = fs::symlink_metadata(path).ok()?.rdev() as libc::dev_t;
+    Some(format!("{}:{}", libc::major(rdev), libc::minor(rdev)))
+}
+
+#[cfg(not(unix))]
+fn device(_path: &Path) -> Option<String> {
+    None
+}
+
+/// How two entries of which at least one is not a regular file differ:
+/// by kind, symlink target or device number.
+fn kind_diff(a: &Path, b: &Path, kinds: (&str, &str)) -> Option<AttrDiff> {
+    match kinds {
+        (x, y) if x != y => Some(AttrDiff::new("type", Some(x.to_string()), Some(y.to_string()))),
+        ("symlink", _) => {
+            let target = |path: &Path| fs::read_link(path).ok().map(|t| t.display().to_string());
+            let (x, y) = (target(a), target(b));
+            (x != y).then(|| AttrDiff::new("target", x, y))
+        }
+        ("block-device" | "char-device", _) => {
+            let (x, y) = (device(a), device(b));
+            (x != y).then(|| AttrDiff::new("device", x, y))
+        }
+        _ => None,
+    }
+}
+
+/// Combined size of `files` below `root`, skipping any that cannot be stat'ed.
+fn total_size<'a>(root: &Path, files: impl IntoIterator<Item = &'a PathBuf>) -> u64 {
+    files.into_iter().filter_map(|rel| file_size(&root.join(rel))).sum()
+}
+
+/// Order in which differences are reported within each category.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Byte-wise by path
+    #[default]
+    Path,
+    /// By path, comparing runs of digits numerically (`file2` before `file10`)
+    Natural,
+    /// Largest first, by the bigger of the two sides
+    Size,
+    /// Most recently modified first, by the newer of the two sides
+    Mtime,
+}
+
+impl SortOrder {
+    /// Order of two differences; ties are broken by path.
+    pub fn cmp_entries(self, x: &DiffEntry, y: &DiffEntry) -> Ordering {
+        let by_path = || self.cmp_paths(&x.path, &y.path);
+        match self {
+            SortOrder::Size => y.size_a.max(y.size_b).cmp(&x.size_a.max(x.size_b)).then_with(by_path),
+            SortOrder::Mtime => y.mtime_a.max(y.mtime_b).cmp(&x.mtime_a.max(x.mtime_b)).then_with(by_path),
+            SortOrder::Path | SortOrder::Natural => by_path(),
+        }
+    }
+
+    /// Order of two paths; subdirectories are always listed by path.
+    pub fn cmp_paths(self, a: &Path, b: &Path) -> Ordering {
+        match self {
+            SortOrder::Path | SortOrder::Size | SortOrder::Mtime => a.cmp(b),
+            SortOrder::Natural => {
+                let mut a = a.components();
+                let mut b = b.components();
+                loop {
+                    match (a.next(), b.next()) {
+                        (Some(x), Some(y)) => {
+                            let ord = natural_cmp(&x.as_os_str().to_string_lossy(), &y.as_os_str().to_string_lossy());
+                            if ord != Ordering::Equal {
+                                return ord;
+                            }
+                        }
+                        (x, y) => return x.is_some().cmp(&y.is_some()),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Version-aware comparison of two strings: digit runs compare by value.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let da = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let db = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            let (na, nb) = (a[..da].trim_start_matches('0'), b[..db].trim_start_matches('0'));
+            // Longer digit runs are larger; equal lengths compare lexically.
+            let ord = na.len().cmp(&nb.len()).then_with(|| na.cmp(nb)).then_with(|| da.cmp(&db));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            (a, b) = (&a[da..], &b[db..]);
+        } else {
+            if ca != cb {
+                return ca.cmp(&cb);
+            }
+            (a, b) = (&a[ca.len_utf8()..], &b[cb.len_utf8()..]);
+        }
+    }
+}
+
+/// Settings that control how two trees are compared.
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    /// Compare the contents of files present on both sides.
+    pub check_hash: bool,
+    /// How contents are compared.
+    pub content: ContentCheck,
+    /// Digest used for the content comparison.
+    pub algo: HashAlgo,
+    /// Bytes hashed at each of the three places sampled by [`ContentCheck::Quick`].
+    pub sample: u64,
+    /// Assume files with equal size and mtime are identical without reading them.
+    pub trust_mtime: bool,
+    /// Compare files larger than this by size and mtime only.
+    pub hash_max_size: Option<u64>,
+    /// Lines of text files left out of the content comparison.
+    pub ignore_lines: Option<RegexSet>,
+    /// Tell binary from text contents for the changed files.
+    pub detect_binary: bool,
+    /// Compare only this many of the most recently modified files of each
+    /// side, per subdirectory.
+    pub latest: Option<usize>,
+    /// When modification times count as equal.
+    pub mtime: MtimeMatch,
+    /// Metadata compared for files present on both sides.
+    pub metadata: MetadataChecks,
+    /// List every file below a directory that exists on one side only,
+    /// instead of a single entry for the directory.
+    pub expand_missing: bool,
+    /// With `expand_missing`, also list every directory missing from the
+    /// other side, not only the empty ones.
+    pub missing_dirs: bool,
+    /// Also report files whose contents were confirmed equal.
+    pub show_same: bool,
+    /// Keep at most this many differences per category and subdirectory;
+    /// the rest are only counted.
+    pub max_diffs: Option<usize>,
+    /// Stop comparing contents once `max_diffs` changed files were found.
+    pub stop_at_max: bool,
+    /// Stop at the first difference.
+    pub fail_fast: bool,
+    /// Walk into symlinked directories instead of comparing the links.
+    pub follow_symlinks: bool,
+    /// Paths taking part in the comparison.
+    pub filter: Filter,
+    /// Report only differences of these categories (all when `None`).
+    pub only: Option<Vec<Category>>,
+    pub sort: SortOrder,
+    /// Advanced for every pair of common files compared.
+    pub progress: Option<Progress>,
+}
+
+impl CompareOptions {
+    /// Whether the files `a` and `b` differ, with their digests if they were
+    /// hashed and the number of bytes read from both.
+    fn compare_contents(&self, a: &Path, b: &Path, cache: &mut DigestCache) -> io::Result<(bool, Option<DigestPair>, u64)> {
+        // Hardlinks between the trees (e.g. rsnapshot) are equal without reading.
+        if self.content != ContentCheck::SizeOnly && same_file(a, b)? {
+            return Ok((false, None, 0));
+        }
+        let result = match self.content {
+            ContentCheck::Hash => contents_differ(a, b, self.algo, cache),
+            ContentCheck::Bytes => bytes_differ(a, b).map(|(differ, read)| (differ, None, read)),
+            ContentCheck::Quick => {
+                if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+                    return Ok((true, None, 0));
+                }
+                // Sample digests are not file digests, so they are not reported.
+                let ((da, ra), (db, rb)) = (hash_sampled(a, self.algo, self.sample)?, hash_sampled(b, self.algo, self.sample)?);
+                Ok((da != db, None, ra + rb))
+            }
+            ContentCheck::SizeOnly => Ok((fs::metadata(a)?.len() != fs::metadata(b)?.len(), None, 0)),
+        };
+        // Files that differ may still be equal apart from the ignored lines.
+        match (result, &self.ignore_lines) {
+            (Ok((true, digests, read)), Some(ignore)) if self.content != ContentCheck::SizeOnly => {
+                let (differ, text_read) = lines_differ(a, b, ignore)?;
+                Ok((differ, digests.filter(|_| differ), read + text_read))
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Whether two modification times count as equal.
+    pub fn same_mtime(&self, a: Option<SystemTime>, b: Option<SystemTime>) -> bool {
+        a.zip(b).is_some_and(|(a, b)| self.mtime.matches(a, b))
+    }
+
+    /// The selected metadata attributes that differ between the files `a` and
+    /// `b`, their modification times included with `--mtime`.
+    pub fn compare_metadata(&self, a: &Path, b: &Path) -> io::Result<Vec<AttrDiff>> {
+        let mut attrs = self.metadata.compare(a, b)?;
+        let (mtime_a, mtime_b) = (file_mtime(a), file_mtime(b));
+        if self.metadata.mtime && !self.same_mtime(mtime_a, mtime_b) {
+            attrs.push(AttrDiff::new("mtime", mtime_a.map(rfc3339), mtime_b.map(rfc3339)));
+        }
+        Ok(attrs)
+    }
+
+    /// Whether differences of `category` should be reported.
+    pub fn reports(&self, category: Category) -> bool {
+        category == Category::Same || self.only.as_ref().is_none_or(|only| only.contains(&category))
+    }
+}
+
+/// Number of files present below both `dir_a` and `dir_b`, and their total
+/// size on both sides: the work `--hash` has ahead of it.
+pub fn common_files(dir_a: &Path, dir_b: &Path, follow_symlinks: bool, filter: &Filter) -> (u64, u64) {
+    let files_a = walk(dir_a, follow_symlinks, filter).files;
+    let common: Vec<_> = walk(dir_b, follow_symlinks, filter).files.into_iter().filter(|f| files_a.contains(f)).collect();
+    (common.len() as u64, total_size(dir_a, &common) + total_size(dir_b, &common))
+}
+
+/// Missing-file entries for `paths` (present under `present` only), with
+/// files below a directory absent from `other` folded into one entry for the
+/// topmost such directory unless `expand` is set.
+fn missing_entries(present: &Path, other: &Path, paths: Vec<PathBuf>, expand: bool, category: Category) -> Vec<DiffEntry> {
+    let mut groups: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    let mut absent: HashMap<PathBuf, bool> = HashMap::new();
+    for path in paths {
+        let mut key = path.clone();
+        if !expand {
+            // Ancestors from the top down, excluding the root and the file itself.
+            let mut dirs: Vec<_> = path.ancestors().skip(1).filter(|p| !p.as_os_str().is_empty()).collect();
+            dirs.reverse();
+            if let Some(dir) = dirs.into_iter().find(|d| *absent.entry(d.to_path_buf()).or_insert_with(|| !other.join(d).is_dir())) {
+                key = dir.to_path_buf();
+            }
+        }
+        groups.entry(key).or_default().push(path);
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, files)| {
+            let size = Some(total_size(present, &files));
+            // A collapsed directory is as recent as its newest file.
+            let mtime = files.iter().filter_map(|f| file_mtime(&present.join(f))).max();
+            let entry = DiffEntry::new(category, key.clone());
+            let entry = if files.len() == 1 && files[0] == key { entry } else { DiffEntry { files: Some(files.len()), ..entry } };
+            match category {
+                Category::MissingInB => DiffEntry { size_a: size, mtime_a: mtime, ..entry },
+                _ => DiffEntry { size_b: size, mtime_b: mtime, ..entry },
+            }
+        })
+        .collect()
+}
+
+/// Entries for the directories below `present` missing from `other` that no
+/// missing-file entry accounts for: those without any file below them, or
+/// with `expand` and `all`, every one.
+fn missing_dir_entries(present: &Path, dirs: &HashSet<PathBuf>, files: &HashSet<PathBuf>, missing: &HashSet<PathBuf>, expand: bool, all: bool, category: Category) -> Vec<DiffEntry> {
+    let holding: HashSet<&Path> = files.iter().flat_map(|f| f.ancestors().skip(1)).collect();
+    let empty = |d: &Path| !holding.contains(d);
+    let parent_missing = |d: &Path| d.parent().filter(|p| missing.contains(*p)).map(Path::to_path_buf);
+    dirs.iter()
+        .filter(|d| missing.contains(*d))
+        .filter(|d| match parent_missing(d) {
+            // Otherwise the topmost missing directory already stands for it.
+            Some(parent) => expand && (all || (empty(d) && !empty(&parent))),
+            None => expand && all || empty(d),
+        })
+        .map(|d| {
+            let entry = DiffEntry { files: Some(0), ..DiffEntry::new(category, d.clone()) };
+            let (size, mtime) = (Some(0), file_mtime(&present.join(d)));
+            match category {
+                Category::MissingInB => DiffEntry { size_a: size, mtime_a: mtime, ..entry },
+                _ => DiffEntry { size_b: size, mtime_b: mtime, ..entry },
+            }
+        })
+        .collect()
+}
+
+/// Compare the files below `dir_a` and `dir_b` and collect the differences
+/// into a report whose `name` is left empty.
+///
+/// `on_entry` is called for every difference as soon as it is discovered;
+/// the returned entries are ordered by category, then by path.
+pub fn compare_dirs(
+    dir_a: &Path,
+    dir_b: &Path,
+    options: &CompareOptions,
+    on_entry: &mut dyn FnMut(&DiffEntry) -> io::Result<()>,
+) -> io::Result<SubdirReport> {
+    let walk_start = Instant::now();
+    let Walk { files: files_a, dirs: dirs_a } = walk(dir_a, options.follow_symlinks, &options.filter);
+    let Walk { files: files_b, dirs: dirs_b } = walk(dir_b, options.follow_symlinks, &options.filter);
+    let (files_a, dirs_a, files_b, dirs_b) = match options.latest {
+        Some(n) => {
+            let mut selected = newest(dir_a, &files_a, n);
+            selected.extend(newest(dir_b, &files_b, n));
+            let (files_a, dirs_a) = keep_files(files_a, dirs_a, &selected);
+            let (files_b, dirs_b) = keep_files(files_b, dirs_b, &selected);
+            (files_a, dirs_a, files_b, dirs_b)
+        }
+        None => (files_a, dirs_a, files_b, dirs_b),
+    };
+    // Dangling symlinks are reported on their own, not compared.
+    let (dangling_a, files_a): (HashSet<_>, HashSet<_>) = files_a.into_iter().partition(|f| is_dangling(&dir_a.join(f)));
+    let (dangling_b, files_b): (HashSet<_>, HashSet<_>) = files_b.into_iter().partition(|f| is_dangling(&dir_b.join(f)));
+    info!(a = %dir_a.display(), b = %dir_b.display(), files_a = files_a.len(), files_b = files_b.len(), elapsed = ?walk_start.elapsed(), "walked");
+    let mut stats = Stats {
+        files_a: files_a.len(),
+        files_b: files_b.len(),
+        bytes_a: total_size(dir_a, &files_a),
+        bytes_b: total_size(dir_b, &files_b),
+        walk_secs: walk_start.elapsed().as_secs_f64(),
+        ..Stats::default()
+    };
+    let mut entries = Vec::new();
+    let mut omitted: BTreeMap<Category, usize> = BTreeMap::new();
+    let mut kept: HashMap<Category, usize> = HashMap::new();
+    let found = Cell::new(false);
+    let mut stopped = false;
+    // Returns `false` once the entry's category has gone past `--max-diffs`.
+    let mut report = |entry: DiffEntry| -> io::Result<bool> {
+        if !options.reports(entry.category) {
+            return Ok(true);
+        }
+        if entry.category != Category::Same {
+            found.set(true);
+        }
+        let kept = kept.entry(entry.category).or_default();
+        if options.max_diffs.is_some_and(|max| *kept >= max) {
+            *omitted.entry(entry.category).or_default() += entry.files.unwrap_or(1);
+            return Ok(false);
+        }
+        *kept += 1;
+        on_entry(&entry)?;
+        entries.push(entry);
+        Ok(true)
+    };
+
+    // Paths holding a file (or symlink, ...) on one side and a directory on
+    // the other are reported once, instead of as missing on both sides.
+    let mut conflicts: Vec<_> = files_a.intersection(&dirs_b).chain(dirs_a.intersection(&files_b)).cloned().collect();
+    conflicts.sort_by(|x, y| options.sort.cmp_paths(x, y));
+    let below_conflict = |path: &PathBuf| conflicts.iter().any(|c| path.starts_with(c));
+
+    // Missing files
+    // A link dangling on one side is reported as such, not as missing there.
+    let missing_in_b: Vec<_> = files_a.difference(&files_b).filter(|f| !below_conflict(f) && !dangling_b.contains(*f)).cloned().collect();
+    let mut missing = missing_entries(dir_a, dir_b, missing_in_b, options.expand_missing, Category::MissingInB);
+    let dirs_missing_in_b = dirs_a.iter().filter(|d| !dirs_b.contains(*d) && !below_conflict(d)).cloned().collect();
+    missing.extend(missing_dir_entries(dir_a, &dirs_a, &files_a, &dirs_missing_in_b, options.expand_missing, options.missing_dirs, Category::MissingInB));
+    missing.sort_by(|x, y| options.sort.cmp_entries(x, y));
+    for entry in missing {
+        if options.fail_fast && found.get() {
+            stopped = true;
+            break;
+        }
+        report(entry)?;
+    }
+
+    let missing_in_a: Vec<_> = files_b.difference(&files_a).filter(|f| !below_conflict(f) && !dangling_a.contains(*f)).cloned().collect();
+    let mut missing = missing_entries(dir_b, dir_a, missing_in_a, options.expand_missing, Category::MissingInA);
+    let dirs_missing_in_a = dirs_b.iter().filter(|d| !dirs_a.contains(*d) && !below_conflict(d)).cloned().collect();
+    missing.extend(missing_dir_entries(dir_b, &dirs_b, &files_b, &dirs_missing_in_a, options.expand_missing, options.missing_dirs, Category::MissingInA));
+    missing.sort_by(|x, y| options.sort.cmp_entries(x, y));
+    for entry in missing {
+        if options.fail_fast && found.get() {
+            stopped = true;
+            break;
+        }
+        report(entry)?;
+    }
+
+    for rel in &conflicts {
+        if options.fail_fast && found.get() {
+            stopped = true;
+            break;
+        }
+        let (pa, pb) = (dir_a.join(rel), dir_b.join(rel));
+        // A directory's size is that of the files below it.
+        let size = |root: &Path, files: &HashSet<PathBuf>, path: &Path| {
+            if path.is_dir() { total_size(root, files.iter().filter(|f| f.starts_with(rel))) } else { file_size(path).unwrap_or(0) }
+        };
+        let (kind_a, kind_b) = (file_kind(&pa), file_kind(&pb));
+        report(DiffEntry {
+            size_a: Some(size(dir_a, &files_a, &pa)),
+            size_b: Some(size(dir_b, &files_b, &pb)),
+            mtime_a: file_mtime(&pa),
+            mtime_b: file_mtime(&pb),
+            attrs: vec![AttrDiff::new("type", Some(kind_a.to_string()), Some(kind_b.to_string()))],
+            ..DiffEntry::new(Category::TypeConflict, rel.clone())
+        })?;
+    }
+
+    for (root, dangling, category) in [(dir_a, dangling_a, Category::DanglingInA), (dir_b, dangling_b, Category::DanglingInB)] {
+        let mut dangling: Vec<_> = dangling.into_iter().collect();
+        dangling.sort_by(|x, y| options.sort.cmp_paths(x, y));
+        for rel in dangling {
+            if options.fail_fast && found.get() {
+                stopped = true;
+                break;
+            }
+            let path = root.join(&rel);
+            let target = fs::read_link(&path).ok().map(|t| t.display().to_string());
+            let (size, mtime) = (file_size(&path), file_mtime(&path));
+            let entry = DiffEntry::new(category, rel);
+            report(match category {
+                Category::DanglingInA => DiffEntry { size_a: size, mtime_a: mtime, attrs: vec![AttrDiff::new("target", target, None)], ..entry },
+                _ => DiffEntry { size_b: size, mtime_b: mtime, attrs: vec![AttrDiff::new("target", None, target)], ..entry },
+            })?;
+        }
+    }
+
+    // Common files (present in both) to check content and metadata equality (optional)
+    let hash_start = Instant::now();
+    let mut cache = DigestCache::default();
+    if (options.check_hash || options.metadata.any()) && !stopped {
+        // Stat both sides up front so the candidates can be sorted by size or mtime.
+        let mut common: Vec<DiffEntry> = files_a
+            .intersection(&files_b)
+            .map(|rel| {
+                let (pa, pb) = (dir_a.join(rel), dir_b.join(rel));
+                DiffEntry {
+                    size_a: file_size(&pa),
+                    size_b: file_size(&pb),
+                    mtime_a: file_mtime(&pa),
+                    mtime_b: file_mtime(&pb),
+                    ..DiffEntry::new(Category::Changed, rel.clone())
+                }
+            })
+            .collect();
+        common.sort_by(|x, y| options.sort.cmp_entries(x, y));
+        for candidate in common {
+            if stopped || (options.fail_fast && found.get()) {
+                stopped = true;
+                break;
+            }
+            let trusted = options.trust_mtime
+                && candidate.size_a.is_some()
+                && candidate.size_a == candidate.size_b
+                && options.same_mtime(candidate.mtime_a, candidate.mtime_b);
+            let (pa, pb) = (dir_a.join(&candidate.path), dir_b.join(&candidate.path));
+            // Symlinks and special files have no contents to read: symlinks
+            // are compared by target, device nodes by number.
+            let kinds = (file_kind(&pa), file_kind(&pb));
+            if kinds != ("file", "file") {
+                if let Some(progress) = &options.progress {
+                    progress.advance(0);
+                }
+                let mut attrs: Vec<_> = kind_diff(&pa, &pb, kinds).into_iter().collect();
+                let changed = !attrs.is_empty();
+                if !changed && kinds.0 != "symlink" {
+                    match options.compare_metadata(&pa, &pb) {
+                        Ok(differing) => attrs = differing,
+                        Err(e) => {
+                            report(DiffEntry { category: Category::Errored, error: Some(e.to_string()), ..candidate })?;
+                            continue;
+                        }
+                    }
+                }
+                let category = if kinds.0 != kinds.1 {
+                    Category::TypeConflict
+                } else if changed {
+                    Category::Changed
+                } else if !attrs.is_empty() {
+                    Category::Metadata
+                } else {
+                    Category::Same
+                };
+                if category != Category::Same || options.show_same {
+                    let kept = report(DiffEntry { category, attrs, ..candidate })?;
+                    stopped = category != Category::Same && !kept && options.stop_at_max;
+                }
+                continue;
+            }
+            let unchecked = options.check_hash
+                && !trusted
+                && options.hash_max_size.is_some_and(|max| candidate.size_a.max(candidate.size_b).unwrap_or(0) > max);
+            let result = if trusted || !options.check_hash {
+                Ok((false, None, 0))
+            } else if unchecked {
+                stats.unchecked += 1;
+                let differ = candidate.size_a != candidate.size_b || !options.same_mtime(candidate.mtime_a, candidate.mtime_b);
+                Ok((differ, None, 0))
+            } else {
+                options.compare_contents(&pa, &pb, &mut cache)
+            };
+            if let Some(progress) = &options.progress {
+                progress.advance(candidate.size_a.unwrap_or(0) + candidate.size_b.unwrap_or(0));
+            }
+            if let Ok((_, _, read)) = result {
+                stats.bytes_hashed += read;
+            }
+            let result = result.and_then(|(differ, digests, _)| Ok((differ, digests, options.compare_metadata(&pa, &pb)?)));
+            match result {
+                Ok((differ, digests, attrs)) if differ || !attrs.is_empty() => {
+                    // Only sniffed for the content diffs, which leave binary files out.
+                    let sniff = options.detect_binary && differ && !unchecked && options.content != ContentCheck::SizeOnly;
+                    let (binary, error) = match sniff.then(|| Ok::<_, io::Error>(is_binary(&pa)? || is_binary(&pb)?)) {
+                        Some(Ok(binary)) => (Some(binary), None),
+                        Some(Err(e)) => (None, Some(format!("could not check for binary contents: {e}"))),
+                        None => (None, None),
+                    };
+                    let kept = report(DiffEntry {
+                        category: if differ { Category::Changed } else { Category::Metadata },
+                        digest_a: digests.as_ref().map(|d| to_hex(&d.0)),
+                        digest_b: digests.as_ref().map(|d| to_hex(&d.1)),
+                        attrs,
+                        unchecked,
+                        binary,
+                        error,
+                        ..candidate
+                    })?;
+                    stopped = !kept && options.stop_at_max;
+                }
+                Ok((_, digests, _)) if options.show_same => {
+                    report(DiffEntry {
+                        category: Category::Same,
+                        digest_a: digests.as_ref().map(|d| to_hex(&d.0)),
+                        digest_b: digests.as_ref().map(|d| to_hex(&d.1)),
+                        unchecked,
+                        ..candidate
+                    })?;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    report(DiffEntry { category: Category::Errored, error: Some(e.to_string()), ..candidate })?;
+                }
+            }
+        }
+    }
+
+    // Common directories, when their metadata is compared too; listed with
+    // a trailing slash.
+    if options.metadata.covers_dirs() && !stopped {
+        let mut common: Vec<_> = dirs_a.intersection(&dirs_b).cloned().collect();
+        common.sort_by(|x, y| options.sort.cmp_paths(x, y));
+        for rel in common {
+            if options.fail_fast && found.get() {
+                stopped = true;
+                break;
+            }
+            let path = PathBuf::from(format!("{}/", rel.display()));
+            let (pa, pb) = (dir_a.join(&rel), dir_b.join(&rel));
+            let (mtime_a, mtime_b) = (file_mtime(&pa), file_mtime(&pb));
+            let result = options.metadata.compare(&pa, &pb).map(|mut attrs| {
+                if options.metadata.dir_mtime && !options.same_mtime(mtime_a, mtime_b) {
+                    attrs.push(AttrDiff::new("mtime", mtime_a.map(rfc3339), mtime_b.map(rfc3339)));
+                }
+                attrs
+            });
+            match result {
+                Ok(attrs) if !attrs.is_empty() => {
+                    report(DiffEntry { attrs, mtime_a, mtime_b, ..DiffEntry::new(Category::Metadata, path) })?;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    report(DiffEntry { error: Some(e.to_string()), ..DiffEntry::new(Category::Errored, path) })?;
+                }
+            }
+        }
+    }
+
+    if options.check_hash {
+        stats.hash_secs = hash_start.elapsed().as_secs_f64();
+        info!(bytes = stats.bytes_hashed, elapsed = ?hash_start.elapsed(), "compared contents");
+    }
+
+    // Stable sort: keeps the path order within each category.
+    entries.sort_by_key(|e| e.category);
+    Ok(SubdirReport { name: PathBuf::new(), status: SubdirStatus::Compared, entries, stats, omitted, stopped })
+}
+
+/// Compare one direct subdirectory `sub` of the two roots.
+pub fn compare_subdir(
+    dir_a: &Path,
+    dir_b: &Path,
+    sub: &Path,
+    options: &CompareOptions,
+    on_entry: &mut dyn FnMut(&DiffEntry) -> io::Result<()>,
+) -> io::Result<SubdirReport> {
+    let path_a = dir_a.join(sub);
+    let path_b = dir_b.join(sub);
+    let options = &CompareOptions { filter: options.filter.under(sub), ..options.clone() };
+
+    let (kind_a, kind_b) = (file_kind(&path_a), file_kind(&path_b));
+    let (is_dir_a, is_dir_b) = (is_subdir(&path_a, options.follow_symlinks), is_subdir(&path_b, options.follow_symlinks));
+    if kind_a != "unknown" && kind_b != "unknown" {
+        if is_dir_a != is_dir_b {
+            return type_conflict(&path_a, &path_b, sub, options, on_entry);
+        }
+        if !is_dir_a {
+            return link_subdir(&path_a, &path_b, sub, on_entry);
+        }
+        return Ok(SubdirReport { name: sub.to_path_buf(), ..compare_dirs(&path_a, &path_b, options, on_entry)? });
+    }
+
+    let start = Instant::now();
+    let (status, stats) = if kind_a != "unknown" {
+        let (files_a, bytes_a) = side_stats(&path_a, options);
+        (SubdirStatus::OnlyInA, Stats { files_a, bytes_a, walk_secs: start.elapsed().as_secs_f64(), ..Stats::default() })
+    } else {
+        let (files_b, bytes_b) = side_stats(&path_b, options);
+        (SubdirStatus::OnlyInB, Stats { files_b, bytes_b, walk_secs: start.elapsed().as_secs_f64(), ..Stats::default() })
+    };
+
+    Ok(SubdirReport::new(sub.to_path_buf(), status, Vec::new(), stats))
+}
+
+/// Files and bytes below the subdirectory at `path`; a single file for a
+/// symlink that is not followed, or anything else that is not a directory.
+fn side_stats(path: &Path, options: &CompareOptions) -> (usize, u64) {
+    if !is_subdir(path, options.follow_symlinks) {
+        return (1, file_size(path).unwrap_or(0));
+    }
+    let files = walk(path, options.follow_symlinks, &options.filter).files;
+    (files.len(), total_size(path, &files))
+}
+
+/// A subdirectory `sub` that is a symlink on both sides, compared by target
+/// like the symlinks below it.
+fn link_subdir(
+    path_a: &Path,
+    path_b: &Path,
+    sub: &Path,
+    on_entry: &mut dyn FnMut(&DiffEntry) -> io::Result<()>,
+) -> io::Result<SubdirReport> {
+    let (size_a, size_b) = (file_size(path_a), file_size(path_b));
+    let mut entries = Vec::new();
+    if let Some(attr) = kind_diff(path_a, path_b, ("symlink", "symlink")) {
+        let entry = DiffEntry {
+            size_a,
+            size_b,
+            mtime_a: file_mtime(path_a),
+            mtime_b: file_mtime(path_b),
+            attrs: vec![attr],
+            ..DiffEntry::new(Category::Changed, PathBuf::from("."))
+        };
+        on_entry(&entry)?;
+        entries.push(entry);
+    }
+    let stats = Stats { files_a: 1, files_b: 1, bytes_a: size_a.unwrap_or(0), bytes_b: size_b.unwrap_or(0), ..Stats::default() };
+    Ok(SubdirReport::new(sub.to_path_buf(), SubdirStatus::Compared, entries, stats))
+}
+
+/// A subdirectory `sub` that is a directory on one side but a file or a
+/// symlink that is not followed at `path_a` or `path_b` on the other: a single
+/// [`Category::TypeConflict`] entry for the subdirectory itself.
+fn type_conflict(
+    path_a: &Path,
+    path_b: &Path,
+    sub: &Path,
+    options: &CompareOptions,
+    on_entry: &mut dyn FnMut(&DiffEntry) -> io::Result<()>,
+) -> io::Result<SubdirReport> {
+    let start = Instant::now();
+    let ((files_a, bytes_a), (files_b, bytes_b)) = (side_stats(path_a, options), side_stats(path_b, options));
+    let entry = DiffEntry {
+        size_a: Some(bytes_a),
+        size_b: Some(bytes_b),
+        mtime_a: file_mtime(path_a),
+        mtime_b: file_mtime(path_b),
+        attrs: vec![AttrDiff::new("type", Some(file_kind(path_a).to_string()), Some(file_kind(path_b).to_string()))],
+        ..DiffEntry::new(Category::TypeConflict, PathBuf::from("."))
+    };
+    on_entry(&entry)?;
+    let stats = Stats { files_a, files_b, bytes_a, bytes_b, walk_secs: start.elapsed().as_secs_f64(), ..Stats::default() };
+    Ok(SubdirReport::new(sub.to_path_buf(), SubdirStatus::Compared, vec![entry], stats))
+}
+
+/// What a manifest or specification says about one file.
+#[derive(Debug, Default, Clone)]
+pub struct Expected {
+    pub size: Option<u64>,
+    /// Lowercase hex digest.
+    pub digest: Option<String>,
+    /// Further metadata to check, by mtree(5) keyword: `mode`, `uid`, `gid`
+    /// and `time`, normalized as [`crate::mtree`] writes them.
+    pub metadata: BTreeMap<String, String>,
+    /// Target of a symbolic link, which is checked instead of contents.
+    pub link: Option<String>,
+}
+
+/// Receives the digests of each file hashed by [`verify_files`].
+pub type OnDigests<'a> = dyn FnMut(&Path, &[Vec<u8>]) + 'a;
+
+/// Check the regular files below `root` against an expected listing of
+/// `algos[0]` digests.
+///
+/// Files listed but not on disk are reported as [`Category::MissingInB`],
+/// files on disk but not listed as [`Category::MissingInA`], and size or
+/// digest mismatches as [`Category::Changed`]. Listed symbolic links are
+/// compared by target, and reported as [`Category::TypeConflict`] when the
+/// path on disk is not a link.
+///
+/// With `on_digests`, every file on disk is hashed in all of `algos` during
+/// the same read and passed to it, e.g. to write a new manifest.
+pub fn verify_files(
+    root: &Path,
+    expected: &BTreeMap<PathBuf, Expected>,
+    algos: &[HashAlgo],
+    mut on_digests: Option<&mut OnDigests>,
+) -> SubdirReport {
+    let walk_start = Instant::now();
+    let on_disk = collect_regular_files(root);
+    let mut stats = Stats {
+        files_a: expected.len(),
+        files_b: on_disk.len(),
+        bytes_a: expected.values().filter_map(|e| e.size).sum(),
+        bytes_b: total_size(root, &on_disk),
+        walk_secs: walk_start.elapsed().as_secs_f64(),
+        ..Stats::default()
+    };
+    let hash_start = Instant::now();
+    let mut entries = Vec::new();
+
+    for (rel, want) in expected {
+        let path = root.join(rel);
+        if let Some(target) = &want.link {
+            let entry = match (file_kind(&path), fs::read_link(&path)) {
+                ("symlink", Ok(actual)) if actual.to_string_lossy() == target.as_str() => continue,
+                ("symlink", actual) => DiffEntry {
+                    attrs: vec![AttrDiff::new("target", Some(target.clone()), actual.ok().map(|t| t.display().to_string()))],
+                    ..DiffEntry::new(Category::Changed, rel.clone())
+                },
+                ("unknown", _) => DiffEntry::new(Category::MissingInB, rel.clone()),
+                (kind, _) => DiffEntry {
+                    attrs: vec![AttrDiff::new("type", Some("symlink".to_string()), Some(kind.to_string()))],
+                    ..DiffEntry::new(Category::TypeConflict, rel.clone())
+                },
+            };
+            entries.push(entry);
+            continue;
+        }
+        if !on_disk.contains(rel) {
+            entries.push(DiffEntry { size_a: want.size, ..DiffEntry::new(Category::MissingInB, rel.clone()) });
+            continue;
+        }
+        let size_b = file_size(&path);
+        let mut changed = want.size.is_some_and(|s| Some(s) != size_b);
+        let mut digest_b = None;
+        if (!changed && want.digest.is_some()) || on_digests.is_some() {
+            match hash_file_multi(&path, algos) {
+                Ok(digests) => {
+                    stats.bytes_hashed += size_b.unwrap_or(0);
+                    if let Some(on_digests) = on_digests.as_mut() {
+                        on_digests(rel, &digests);
+                    }
+                    if let Some(digest) = &want.digest {
+                        let actual = to_hex(&digests[0]);
+                        changed = changed || &actual != digest;
+                        digest_b = Some(actual);
+                    }
+                }
+                Err(e) => {
+                    entries.push(DiffEntry { size_a: want.size, size_b, error: Some(e.to_string()), ..DiffEntry::new(Category::Errored, rel.clone()) });
+                    continue;
+                }
+            }
+        }
+        if changed {
+            entries.push(DiffEntry {
+                size_a: want.size,
+                size_b,
+                digest_a: want.digest.clone(),
+                digest_b,
+                ..DiffEntry::new(Category::Changed, rel.clone())
+            });
+        }
+    }
+
+    let mut extra: Vec<_> = on_disk.into_iter().filter(|rel| !expected.contains_key(rel)).collect();
+    extra.sort();
+    for rel in extra {
+        let path = root.join(&rel);
+        let size_b = file_size(&path);
+        if let Some(on_digests) = on_digests.as_mut() {
+            match hash_file_multi(&path, algos) {
+                Ok(digests) => {
+                    stats.bytes_hashed += size_b.unwrap_or(0);
+                    on_digests(&rel, &digests);
+                }
+                Err(e) => {
+                    entries.push(DiffEntry { size_b, error: Some(e.to_string()), ..DiffEntry::new(Category::Errored, rel) });
+                    continue;
+                }
+            }
+        }
+        entries.push(DiffEntry { size_b, ..DiffEntry::new(Category::MissingInA, rel) });
+    }
+    stats.hash_secs = hash_start.elapsed().as_secs_f64();
+
+    entries.sort_by_key(|e| e.category);
+    SubdirReport::new(PathBuf::from("."), SubdirStatus::Compared, entries, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::MtimePrecision;
+    use std::time::Duration;
+
+    /// An empty scratch directory for the test called `name`.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dirdiff-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("a")).unwrap();
+        fs::create_dir_all(dir.join("b")).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn link_dangling_on_one_side_is_not_missing() {
+        let dir = scratch("dangling");
+        fs::write(dir.join("b/target"), "x").unwrap();
+        std::os::unix::fs::symlink("target", dir.join("a/l")).unwrap();
+        std::os::unix::fs::symlink("target", dir.join("b/l")).unwrap();
+        fs::write(dir.join("a/target-a-only"), "x").unwrap();
+
+        let report = compare_dirs(&dir.join("a"), &dir.join("b"), &CompareOptions::default(), &mut |_| Ok(())).unwrap();
+        let paths = |category| report.entries_in(category).map(|e| e.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(Category::DanglingInA), [PathBuf::from("l")]);
+        assert_eq!(paths(Category::MissingInA), [PathBuf::from("target")]);
+        assert_eq!(paths(Category::MissingInB), [PathBuf::from("target-a-only")]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn subdir_that_is_a_file_on_the_other_side_is_a_type_conflict() {
+        let dir = scratch("top-level-conflict");
+        fs::write(dir.join("a/foo"), "x").unwrap();
+        fs::create_dir(dir.join("b/foo")).unwrap();
+        fs::write(dir.join("b/foo/inner"), "y").unwrap();
+
+        let sub = compare_subdir(&dir.join("a"), &dir.join("b"), Path::new("foo"), &CompareOptions::default(), &mut |_| Ok(())).unwrap();
+        assert_eq!(sub.status, SubdirStatus::Compared);
+        assert_eq!(sub.entries.len(), 1);
+        assert_eq!(sub.entries[0].category, Category::TypeConflict);
+        assert_eq!(sub.entries[0].kinds(), ("file", "dir"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn top_level_links_are_compared_as_links_unless_followed() {
+        let dir = scratch("top-level-links");
+        for side in ["a", "b"] {
+            fs::create_dir(dir.join(side).join("real")).unwrap();
+            fs::create_dir(dir.join(side).join("other")).unwrap();
+        }
+        fs::write(dir.join("a/real/f"), "x").unwrap();
+        std::os::unix::fs::symlink("real", dir.join("a/l")).unwrap();
+        std::os::unix::fs::symlink("other", dir.join("b/l")).unwrap();
+
+        let compare = |follow_symlinks| {
+            let options = CompareOptions { follow_symlinks, ..CompareOptions::default() };
+            compare_subdir(&dir.join("a"), &dir.join("b"), Path::new("l"), &options, &mut |_| Ok(())).unwrap()
+        };
+        let sub = compare(false);
+        assert_eq!(sub.entries.len(), 1);
+        assert_eq!(sub.entries[0].category, Category::Changed);
+        assert_eq!(sub.entries[0].attrs, [AttrDiff::new("target", Some("real".into()), Some("other".into()))]);
+        let sub = compare(true);
+        assert_eq!(sub.entries.len(), 1);
+        assert_eq!(sub.entries[0].category, Category::MissingInB);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn mtime_check_honors_the_mtime_precision() {
+        let dir = scratch("mtime-precision");
+        let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        for (side, mtime) in [("a", epoch), ("b", epoch + Duration::from_millis(500))] {
+            fs::write(dir.join(side).join("f"), "x").unwrap();
+            fs::File::options().write(true).open(dir.join(side).join("f")).unwrap().set_modified(mtime).unwrap();
+        }
+
+        let compare = |precision| {
+            let options = CompareOptions {
+                metadata: MetadataChecks { mtime: true, ..MetadataChecks::default() },
+                mtime: MtimeMatch { precision, ..MtimeMatch::default() },
+                ..CompareOptions::default()
+            };
+            compare_dirs(&dir.join("a"), &dir.join("b"), &options, &mut |_| Ok(())).unwrap().count(Category::Metadata)
+        };
+        assert_eq!(compare(MtimePrecision::Ns), 1);
+        assert_eq!(compare(MtimePrecision::S), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn trust_mtime_honors_the_mtime_window() {
+        let dir = scratch("trust-mtime");
+        let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        for (side, contents, mtime) in [("a", "aaa", epoch), ("b", "bbb", epoch + Duration::from_secs(1))] {
+            fs::write(dir.join(side).join("f"), contents).unwrap();
+            fs::File::options().write(true).open(dir.join(side).join("f")).unwrap().set_modified(mtime).unwrap();
+        }
+
+        let compare = |window| {
+            let options = CompareOptions {
+                check_hash: true,
+                trust_mtime: true,
+                mtime: MtimeMatch { window: Duration::from_secs(window), ..MtimeMatch::default() },
+                ..CompareOptions::default()
+            };
+            compare_dirs(&dir.join("a"), &dir.join("b"), &options, &mut |_| Ok(())).unwrap().count(Category::Changed)
+        };
+        assert_eq!(compare(0), 1);
+        assert_eq!(compare(2), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+//! Broad classes of files, told apart by extension or, failing that, by the
+//! first bytes of their contents.
+
+use std::fs::{File, Metadata};
+use std::io::{self, Read};
+use std::path::Path;
+
+use clap::ValueEnum;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    /// Photos and pictures, including camera raw files
+    Image,
+    /// Music and recordings
+    Audio,
+    /// Movies and clips
+    Video,
+    /// Plain text, source code and markup
+    Text,
+    /// Archives and compressed files
+    Archive,
+}
+
+impl FileType {
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            FileType::Image => &[
+                "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "heif", "avif", "svg", "ico",
+                "psd", "raw", "dng", "cr2", "cr3", "nef", "arw", "orf", "rw2", "raf",
+            ],
+            FileType::Audio => &["mp3", "wav", "flac", "aac", "m4a", "ogg", "oga", "opus", "wma", "aiff", "aif", "alac"],
+            FileType::Video => &["mp4", "m4v", "mov", "mkv", "webm", "avi", "wmv", "flv", "mpg", "mpeg", "mts", "m2ts", "3gp"],
+            FileType::Text => &[
+                "txt", "md", "rst", "csv", "tsv", "json", "yaml", "yml", "toml", "ini", "cfg", "conf", "xml", "html",
+                "htm", "css", "js", "ts", "rs", "py", "rb", "go", "c", "h", "cpp", "hpp", "java", "sh", "sql", "log",
+            ],
+            FileType::Archive => &["zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "jar", "iso", "dmg"],
+        }
+    }
+
+    /// The class of the file at `path`: by its extension when that is a known
+    /// one, otherwise by sniffing its first bytes.
+    pub fn of(path: &Path) -> Option<FileType> {
+        let extension = path.extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        if let Some(ext) = extension {
+            let by_extension = FileType::value_variants().iter().find(|t| t.extensions().contains(&ext.as_str()));
+            if let Some(t) = by_extension {
+                return Some(*t);
+            }
+        }
+        let mut head = Vec::with_capacity(512);
+        File::open(path).and_then(|f| f.take(512).read_to_end(&mut head)).ok()?;
+        sniff(&head)
+    }
+}
+
+/// Whether the regular file at `path` with `meta` is a program: on Unix, one
+/// with an execute permission bit set; elsewhere, one starting with the magic
+/// number of a PE, ELF or Mach-O binary, or with `#!`.
+#[cfg(unix)]
+pub fn is_executable(_path: &Path, meta: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.is_file() && meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+pub fn is_executable(path: &Path, meta: &Metadata) -> bool {
+    const MAGIC: [&[u8]; 6] = [b"MZ", b"\x7FELF", b"\xFE\xED\xFA\xCE", b"\xFE\xED\xFA\xCF", b"\xCF\xFA\xED\xFE", b"#!"];
+    let mut head = [0; 4];
+    meta.is_file()
+        && File::open(path).and_then(|mut f| f.read_exact(&mut head)).is_ok()
+        && MAGIC.iter().any(|magic| head.starts_with(magic))
+}
+
+/// Whether the file at `path` holds binary data rather than text, judging
+/// by its first 8000 bytes: NUL bytes, a known binary magic number or
+/// invalid UTF-8 make it binary.
+pub fn is_binary(path: &Path) -> io::Result<bool> {
+    let mut head = Vec::with_capacity(8000);
+    File::open(path)?.take(8000).read_to_end(&mut head)?;
+    Ok(!head.is_empty() && sniff(&head) != Some(FileType::Text))
+}
+
+/// The class of a file starting with `head`, from well-known magic numbers;
+/// anything else that is valid UTF-8 without NUL bytes counts as text.
+fn sniff(head: &[u8]) -> Option<FileType> {
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+    let riff = |form: &[u8]| at(0, b"RIFF") && at(8, form);
+    let iff = |form: &[u8]| at(0, b"FORM") && at(8, form);
+    if at(0, b"\x89PNG") || at(0, b"\xFF\xD8\xFF") || at(0, b"GIF8") || riff(b"WEBP") || at(0, b"II*\0") || at(0, b"MM\0*") {
+        return Some(FileType::Image);
+    }
+    if at(4, b"ftyp") {
+        return Some(match head.get(8..12) {
+            Some(b"heic" | b"heix" | b"mif1" | b"avif") => FileType::Image,
+            Some(b"M4A " | b"M4B ") => FileType::Audio,
+            _ => FileType::Video,
+        });
+    }
+    if at(0, b"ID3") || at(0, b"fLaC") || at(0, b"OggS") || riff(b"WAVE") || iff(b"AIFF") || iff(b"AIFC") {
+        return Some(FileType::Audio);
+    }
+    if at(0, b"\x1A\x45\xDF\xA3") || riff(b"AVI ") {
+        return Some(FileType::Video);
+    }
+    if at(0, b"PK\x03\x04") || at(0, b"\x1F\x8B") || at(0, b"BZh") || at(0, b"\xFD7zXZ\0") || at(0, b"7z\xBC\xAF\x27\x1C")
+        || at(0, b"Rar!") || at(0, b"\x28\xB5\x2F\xFD") || at(257, b"ustar")
+    {
+        return Some(FileType::Archive);
+    }
+    // A multi-byte character may be cut off at the end of the sample.
+    let text = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    (text && !head.is_empty() && !head.contains(&0)).then_some(FileType::Text)
+}
@@ -0,0 +1,487 @@
+//! Selection of the paths below the roots that take part in a comparison.
+
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::Gitignore;
+use ignore::Match;
+use regex::{Regex, RegexSet};
+use tracing::warn;
+
+use crate::filetype::{is_executable, FileType};
+
+/// Which paths below the roots are walked and compared; everything else is
+/// skipped as if it did not exist. Paths are matched relative to the roots
+/// given on the command line, whichever subdirectory is being compared.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Subdirectory of the roots that walks start from.
+    base: PathBuf,
+    /// Only files matching one of these are compared, when set.
+    include: Option<GlobSet>,
+    /// Files and directories (with everything below them) matching one of
+    /// these are skipped.
+    exclude: Option<GlobSet>,
+    /// Like `include`, for regular expressions; a file matching either is
+    /// compared.
+    include_regex: Option<RegexSet>,
+    /// Like `exclude`, for regular expressions.
+    exclude_regex: Option<RegexSet>,
+    /// Files smaller than this are skipped.
+    min_size: Option<u64>,
+    /// Files larger than this are skipped.
+    max_size: Option<u64>,
+    /// Files last modified at or before this are skipped.
+    newer_than: Option<SystemTime>,
+    /// Files last modified at or after this are skipped.
+    older_than: Option<SystemTime>,
+    /// Only regular files of one of these classes are compared, when not
+    /// empty.
+    types: Vec<FileType>,
+    /// Only executable files are compared.
+    executables_only: bool,
+    /// Skip hidden files and directories.
+    skip_hidden: bool,
+    /// Entries nested deeper than this below the roots are skipped; the
+    /// entries directly in a root are at depth 1.
+    max_depth: Option<usize>,
+    /// Entries nested less deep than this are walked through but skipped.
+    min_depth: Option<usize>,
+    /// Do not descend into directories on another file system than the
+    /// top of the tree.
+    one_file_system: bool,
+    /// Honor `.gitignore` files and the global git excludes.
+    gitignore: bool,
+    /// Honor the `.dirdiffignore` file at the top of each tree.
+    dirdiffignore: bool,
+}
+
+/// Name of the file of gitignore-style patterns loaded from each root.
+const DIRDIFFIGNORE: &str = ".dirdiffignore";
+
+/// A set of the given regular expressions, or `None` when there are none.
+fn regex_set(regexes: &[Regex]) -> Option<RegexSet> {
+    if regexes.is_empty() {
+        return None;
+    }
+    RegexSet::new(regexes.iter().map(Regex::as_str)).ok()
+}
+
+/// A bundle of excludes for the clutter a kind of tree usually contains.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Version control and build output: .git, node_modules, target, ...
+    Dev,
+    /// Finder and Spotlight files: .DS_Store, ._*, .Spotlight-V100, ...
+    Macos,
+    /// Explorer and system files: Thumbs.db, desktop.ini, $RECYCLE.BIN, ...
+    Windows,
+}
+
+impl Preset {
+    /// The globs this preset excludes.
+    pub fn globs(self) -> Vec<Glob> {
+        let patterns: &[&str] = match self {
+            Preset::Dev => &["**/.git", "**/.hg", "**/.svn", "**/node_modules", "**/target", "**/__pycache__", "**/.venv"],
+            Preset::Macos => &["**/.DS_Store", "**/._*", "**/.Spotlight-V100", "**/.Trashes", "**/.fseventsd", "**/.TemporaryItems"],
+            Preset::Windows => &["**/Thumbs.db", "**/desktop.ini", "**/$RECYCLE.BIN", "**/System Volume Information"],
+        };
+        patterns.iter().map(|p| Glob::new(p).expect("preset globs are valid")).collect()
+    }
+}
+
+/// Parse a glob pattern given on the command line.
+pub fn parse_glob(pattern: &str) -> Result<Glob, String> {
+    Glob::new(pattern).map_err(|e| e.to_string())
+}
+
+/// Parse a size in bytes such as `512`, `100M` or `1.5GiB`; `k`, `M`, `G`
+/// and `T` are powers of 1000, `Ki`, `Mi`, `Gi` and `Ti` powers of 1024.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let invalid = || format!("`{s}` is not a size such as 512, 100M or 1.5GiB");
+    let digits = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (number, unit) = s.split_at(digits);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let unit = unit.trim_start().strip_suffix(['B', 'b']).unwrap_or(unit.trim_start());
+    let factor: u64 = match unit {
+        "" => 1,
+        "k" | "K" => 1_000,
+        "M" => 1_000_000,
+        "G" => 1_000_000_000,
+        "T" => 1_000_000_000_000,
+        "Ki" => 1 << 10,
+        "Mi" => 1 << 20,
+        "Gi" => 1 << 30,
+        "Ti" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    Ok((number * factor as f64).round() as u64)
+}
+
+/// Parse a point in time: either a UTC date such as `2024-05-01` or
+/// `2024-05-01T12:00:00`, or an age such as `90m`, `12h`, `7d` or `2w`
+/// before now.
+pub fn parse_time(s: &str) -> Result<SystemTime, String> {
+    let invalid = || format!("`{s}` is neither a date such as 2024-05-01[T12:00:00] nor an age such as 7d");
+    let Some((end, unit)) = s.char_indices().last() else {
+        return Err(invalid());
+    };
+    let unit = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86_400,
+        'w' => 7 * 86_400,
+        _ => 0,
+    };
+    if let (true, Ok(n)) = (unit > 0, s[..end].parse::<u64>()) {
+        let age = n.checked_mul(unit).ok_or_else(invalid)?;
+        return SystemTime::now().checked_sub(Duration::from_secs(age)).ok_or_else(invalid);
+    }
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    let (date, time) = s.split_once(['T', ' ']).unwrap_or((s, "00:00"));
+    let date: Vec<i64> = date.split('-').map(str::parse).collect::<Result<_, _>>().map_err(|_| invalid())?;
+    let time: Vec<i64> = time.split(':').map(str::parse).collect::<Result<_, _>>().map_err(|_| invalid())?;
+    let (year, month, day, hour, minute, second) = match (date.as_slice(), time.as_slice()) {
+        (&[year, month, day], &[hour, minute]) => (year, month, day, hour, minute, 0),
+        (&[year, month, day], &[hour, minute, second]) => (year, month, day, hour, minute, second),
+        _ => return Err(invalid()),
+    };
+    if !(0..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || !(0..=23).contains(&hour)
+        || !(0..=59).contains(&minute)
+        || !(0..=60).contains(&second)
+    {
+        return Err(invalid());
+    }
+
+    // Days since 1970-01-01 from a civil date (Howard Hinnant's algorithm).
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second;
+    let offset = Duration::from_secs(secs.unsigned_abs());
+    if secs >= 0 { UNIX_EPOCH.checked_add(offset) } else { UNIX_EPOCH.checked_sub(offset) }.ok_or_else(invalid)
+}
+
+/// Number of days in `month` (1-12) of `year` in the Gregorian calendar.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// A set of the given globs, or `None` when there are none.
+fn glob_set(globs: &[Glob]) -> Option<GlobSet> {
+    if globs.is_empty() {
+        return None;
+    }
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(glob.clone());
+    }
+    builder.build().ok()
+}
+
+impl Filter {
+    /// Keep only the files matching one of `globs` (all files if empty).
+    pub fn include(self, globs: &[Glob]) -> Filter {
+        Filter { include: glob_set(globs), ..self }
+    }
+
+    /// Skip the files and directories matching one of `globs`.
+    pub fn exclude(self, globs: &[Glob]) -> Filter {
+        Filter { exclude: glob_set(globs), ..self }
+    }
+
+    /// Keep only the files matching one of `regexes`, or one of the included
+    /// globs.
+    pub fn include_regex(self, regexes: &[Regex]) -> Filter {
+        Filter { include_regex: regex_set(regexes), ..self }
+    }
+
+    /// Skip the files and directories matching one of `regexes`.
+    pub fn exclude_regex(self, regexes: &[Regex]) -> Filter {
+        Filter { exclude_regex: regex_set(regexes), ..self }
+    }
+
+    /// Skip the files smaller than `min` or larger than `max` bytes.
+    pub fn size(self, min: Option<u64>, max: Option<u64>) -> Filter {
+        Filter { min_size: min, max_size: max, ..self }
+    }
+
+    /// Skip the files last modified outside the window from `newer_than` to
+    /// `older_than`.
+    pub fn modified(self, newer_than: Option<SystemTime>, older_than: Option<SystemTime>) -> Filter {
+        Filter { newer_than, older_than, ..self }
+    }
+
+    /// Keep only the regular files of one of `types` (all files if empty).
+    pub fn types(self, types: &[FileType]) -> Filter {
+        Filter { types: types.to_vec(), ..self }
+    }
+
+    /// Keep only the executable files.
+    pub fn executables_only(self, executables_only: bool) -> Filter {
+        Filter { executables_only, ..self }
+    }
+
+    /// Skip dotfiles, and on Windows the files with the hidden attribute,
+    /// along with everything below hidden directories.
+    pub fn skip_hidden(self, skip_hidden: bool) -> Filter {
+        Filter { skip_hidden, ..self }
+    }
+
+    /// Skip the entries nested more than `max_depth` levels below the roots.
+    pub fn max_depth(self, max_depth: Option<usize>) -> Filter {
+        Filter { max_depth, ..self }
+    }
+
+    /// Skip the entries nested less than `min_depth` levels below the roots,
+    /// while still walking through them.
+    pub fn min_depth(self, min_depth: Option<usize>) -> Filter {
+        Filter { min_depth, ..self }
+    }
+
+    /// Stay on the file system of each tree's top directory.
+    pub fn one_file_system(self, one_file_system: bool) -> Filter {
+        Filter { one_file_system, ..self }
+    }
+
+    /// Honor `.gitignore` files in the trees and the global git excludes.
+    pub fn gitignore(self, gitignore: bool) -> Filter {
+        Filter { gitignore, ..self }
+    }
+
+    /// Honor the `.dirdiffignore` file at the top of each tree.
+    pub fn dirdiffignore(self, dirdiffignore: bool) -> Filter {
+        Filter { dirdiffignore, ..self }
+    }
+
+    /// The same filter, for walks starting at the subdirectory `sub`.
+    pub fn under(&self, sub: &Path) -> Filter {
+        Filter { base: self.base.join(sub), ..self.clone() }
+    }
+
+    /// Whether the entry at `rel`, relative to the start of the walk, takes
+    /// part in the comparison. Directories are walked regardless, so that
+    /// `**/*.ext` finds files at any depth.
+    pub fn accepts(&self, rel: &Path) -> bool {
+        let path = self.base.join(rel);
+        let depth = depth(&path);
+        !self.excludes(&path)
+            && self.includes(&path)
+            && self.max_depth.is_none_or(|max| depth <= max)
+            && self.min_depth.is_none_or(|min| depth >= min)
+    }
+
+    /// Whether the file (not a directory) at `path` with `meta` is within the
+    /// limits on its size and modification time, and of a wanted type (or
+    /// executable).
+    pub fn accepts_file(&self, path: &Path, meta: &Metadata) -> bool {
+        if self.min_size.is_some_and(|min| meta.len() < min) || self.max_size.is_some_and(|max| meta.len() > max) {
+            return false;
+        }
+        if self.newer_than.is_some() || self.older_than.is_some() {
+            let Ok(mtime) = meta.modified() else {
+                return false;
+            };
+            if self.newer_than.is_some_and(|t| mtime <= t) || self.older_than.is_some_and(|t| mtime >= t) {
+                return false;
+            }
+        }
+        if self.executables_only && !is_executable(path, meta) {
+            return false;
+        }
+        self.types.is_empty() || (meta.is_file() && FileType::of(path).is_some_and(|t| self.types.contains(&t)))
+    }
+
+    /// Whether the walk descends into the directory at `rel`.
+    pub fn descends(&self, rel: &Path) -> bool {
+        let path = self.base.join(rel);
+        !self.excludes(&path) && self.max_depth.is_none_or(|max| depth(&path) < max)
+    }
+
+    /// Whether the direct subdirectory `sub` of `root` is compared at all.
+    pub fn walks_subdir(&self, root: &Path, sub: &Path) -> bool {
+        let dir = root.join(sub);
+        let hidden = fs::symlink_metadata(&dir).is_ok_and(|meta| self.hides(&dir, &meta));
+        let path = self.base.join(sub);
+        let deep = self.max_depth.is_some_and(|max| depth(&path) > max);
+        let mounted = self.tree_device(root).is_some_and(|dev| fs::metadata(&dir).is_ok_and(|meta| device_id(&meta) != Some(dev)));
+        !self.excludes(&path) && !deep && !hidden && !mounted && !Ignores::at_root(&dir, &self.under(sub)).ignored(&dir, true)
+    }
+
+    /// The top directory of the tree that a walk starting at `root` is in.
+    fn top<'a>(&self, root: &'a Path) -> &'a Path {
+        root.ancestors().nth(self.base.components().count()).unwrap_or(root)
+    }
+
+    /// With `one_file_system`, the device of the file system holding the top
+    /// of the tree that a walk starting at `root` is in.
+    pub fn tree_device(&self, root: &Path) -> Option<u64> {
+        if !self.one_file_system {
+            return None;
+        }
+        fs::metadata(self.top(root)).ok().as_ref().and_then(device_id)
+    }
+
+    /// Whether the entry at `path` with `meta` is skipped as hidden.
+    pub fn hides(&self, path: &Path, meta: &Metadata) -> bool {
+        if !self.skip_hidden {
+            return false;
+        }
+        hidden_attribute(meta) || path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+    }
+
+    fn includes(&self, path: &Path) -> bool {
+        if self.include.is_none() && self.include_regex.is_none() {
+            return true;
+        }
+        self.include.as_ref().is_some_and(|include| include.is_match(path))
+            || self.include_regex.as_ref().is_some_and(|include| include.is_match(&path.to_string_lossy()))
+    }
+
+    fn excludes(&self, path: &Path) -> bool {
+        self.exclude.as_ref().is_some_and(|exclude| exclude.is_match(path))
+            || self.exclude_regex.as_ref().is_some_and(|exclude| exclude.is_match(&path.to_string_lossy()))
+    }
+}
+
+/// How many levels below the roots the relative `path` is.
+fn depth(path: &Path) -> usize {
+    path.components().count()
+}
+
+/// The device of the file system holding the entry with `meta`.
+#[cfg(unix)]
+pub fn device_id(meta: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.dev())
+}
+
+#[cfg(not(unix))]
+pub fn device_id(_meta: &Metadata) -> Option<u64> {
+    None
+}
+
+/// Whether the Windows hidden attribute is set.
+#[cfg(windows)]
+fn hidden_attribute(meta: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    meta.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+fn hidden_attribute(_meta: &Metadata) -> bool {
+    false
+}
+
+/// The ignore rules in effect in one directory of a walk: the tree's
+/// `.dirdiffignore`, the global git excludes, then the `.gitignore` of every
+/// directory from the top of the tree down to it.
+#[derive(Debug, Clone, Default)]
+pub struct Ignores {
+    enabled: bool,
+    rules: Vec<Arc<Gitignore>>,
+}
+
+impl Ignores {
+    /// The rules in effect at `root`, where a walk with `filter` starts.
+    pub fn at_root(root: &Path, filter: &Filter) -> Ignores {
+        let mut ignores = Ignores { enabled: filter.gitignore, rules: Vec::new() };
+        // When comparing a subdirectory, the tree starts above the walk.
+        let top = filter.top(root);
+        if filter.dirdiffignore {
+            let path = top.join(DIRDIFFIGNORE);
+            if path.is_file() {
+                ignores.rules.push(Arc::new(load(&path)));
+            }
+        }
+        if !ignores.enabled {
+            return ignores;
+        }
+        let (global, error) = Gitignore::global();
+        if let Some(e) = error {
+            warn!(error = %e, "could not read the global git excludes");
+        }
+        if !global.is_empty() {
+            ignores.rules.push(Arc::new(global));
+        }
+        let mut dir = top.to_path_buf();
+        for component in filter.base.components() {
+            ignores = ignores.enter(&dir);
+            dir.push(component);
+        }
+        ignores
+    }
+
+    /// The rules in effect below `dir`: these plus its own `.gitignore`.
+    pub fn enter(&self, dir: &Path) -> Ignores {
+        let path = dir.join(".gitignore");
+        if !self.enabled || !path.is_file() {
+            return self.clone();
+        }
+        let mut rules = self.rules.clone();
+        rules.push(Arc::new(load(&path)));
+        Ignores { rules, ..self.clone() }
+    }
+
+    /// Whether the file (or, with `is_dir`, directory) at `path` is ignored.
+    /// Like git, the `.git` directory itself always is.
+    pub fn ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self.enabled && path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        // The deepest rule that matches decides, as in git.
+        for rules in self.rules.iter().rev() {
+            match rules.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+/// The rules of a gitignore-style file, as far as they could be read.
+fn load(path: &Path) -> Gitignore {
+    let (gitignore, error) = Gitignore::new(path);
+    if let Some(e) = error {
+        warn!(file = %path.display(), error = %e, "could not read all of the ignore rules");
+    }
+    gitignore
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time_rejects_out_of_range_values() {
+        assert!(parse_time("99999999999999999w").is_err());
+        assert!(parse_time("99999999999-01-01").is_err());
+        assert!(parse_time("2024-05-01T-1:00").is_err());
+        assert!(parse_time("").is_err());
+        assert!(parse_time("é").is_err());
+        assert!(parse_time("2024-02-31").is_err());
+        assert!(parse_time("2023-02-29").is_err());
+        assert!(parse_time("2024-02-29").is_ok());
+        assert_eq!(parse_time("1969-12-31T23:59:59"), Ok(UNIX_EPOCH - Duration::from_secs(1)));
+    }
+}
@@ -1,212 +1,874 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, IsTerminal};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use tracing::{info, Level};
+
+mod bagit;
+mod compare;
+mod filetype;
+mod filter;
+mod manifest;
+mod metadata;
+mod mtree;
+mod output;
+mod progress;
+mod render;
+mod report;
+
+use filetype::FileType;
+use filter::{parse_glob, parse_size, parse_time, Filter, Preset};
+use metadata::MetadataChecks;
+use output::{Compression, Output};
+use compare::{collect_regular_files, common_files, compare_subdir, hash_file_multi, CompareOptions, ContentCheck, HashAlgo, OnDigests, SortOrder, direct_subdirs, verify_files};
+use progress::Progress;
+use render::anonymize::Anonymize;
+use render::content::{terminal_width, ContentDiff, Layout};
+use render::count::CountRenderer;
+use render::print0::Print0Renderer;
+use render::split::SplitRenderer;
+use render::sqlite::SqliteRecorder;
+use render::template::TemplateRenderer;
+use render::top::TopRenderer;
+use render::{render, renderer_for, ColorChoice, Fanout, Format, Options, Palette, Renderer, View};
+use report::{Category, DiffEntry, MtimeMatch, MtimePrecision, Report, SubdirStatus};
 
-use clap::Parser;
-use sha2::{Digest, Sha256};
-
-// ANSI color escape codes (no external crate needed)
-const RED: &str = "\x1b[31m";
-const GREEN: &str = "\x1b[32m";
-const YELLOW: &str = "\x1b[33m";
-const CYAN: &str = "\x1b[36m";
-const RESET: &str = "\x1b[0m";
-
-/// Recursively collects **file** paths (relative to `root`) into a `HashSet`.
-fn collect_files(root: &Path) -> HashSet<PathBuf> {
-    let mut stack = vec![root.to_path_buf()];
-    let mut files = HashSet::new();
-
-    while let Some(current) = stack.pop() {
-        if current.is_dir() {
-            if let Ok(entries) = fs::read_dir(&current) {
-                for entry in entries.flatten() {
-                    stack.push(entry.path());
-                }
-            }
-        } else if current.is_file() {
-            if let Ok(relative) = current.strip_prefix(root) {
-                files.insert(relative.to_path_buf());
-            }
-        }
-    }
+#[derive(Parser, Debug)]
+#[command(
+    name = "dir_compare",
+    version,
+    about = "Compare directory structures (and optionally contents) by subdirectory.",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// First directory to compare
+    #[arg(value_name = "DIRECTORY_A", required = true)]
+    dir_a: Option<PathBuf>,
+    /// Second directory to compare
+    #[arg(value_name = "DIRECTORY_B", required = true)]
+    dir_b: Option<PathBuf>,
+    /// Also compare file contents, using the --algo digest
+    #[arg(long, group = "content")]
+    hash: bool,
+    /// Also compare file contents byte by byte, stopping at the first
+    /// difference instead of hashing both files fully
+    #[arg(long, group = "content")]
+    bytes: bool,
+    /// Also compare file contents by size and --algo digests of their first,
+    /// middle and last KB kilobytes only (--quick=KB, default 64)
+    #[arg(long, group = "content", value_name = "KB", num_args = 0..=1, require_equals = true, default_missing_value = "64",
+        value_parser = clap::value_parser!(u64).range(1..))]
+    quick: Option<u64>,
+    /// Also flag files present on both sides as changed when their sizes
+    /// differ, without reading their contents (like rsync --size-only)
+    #[arg(long, group = "content")]
+    size_only: bool,
+    /// Assume files with the same size and modification time are identical
+    /// and only compare the contents of the others
+    #[arg(long, requires = "content")]
+    trust_mtime: bool,
+    /// Compare files larger than SIZE (e.g. 4G) by size and modification
+    /// time only, and flag them as "skipped content check"
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "content")]
+    hash_max_size: Option<u64>,
+    /// Leave the lines of text files that match REGEX out of the content
+    /// comparison, e.g. '^# Generated' (like diff -I; repeatable)
+    #[arg(long, value_name = "REGEX", requires = "content")]
+    ignore_matching_lines: Vec<regex::bytes::Regex>,
+    /// Treat modification times at most SECONDS apart as equal, e.g. 2 for
+    /// FAT/exFAT or some SMB servers
+    #[arg(long, value_name = "SECONDS", default_value_t = 0.0, value_parser = parse_seconds)]
+    mtime_window: f64,
+    /// Whether sub-second differences between modification times count, for
+    /// --mtime, --dir-mtime, --trust-mtime and the newer/older notes
+    #[arg(long, value_enum, default_value_t = MtimePrecision::Ns)]
+    mtime_precision: MtimePrecision,
+    /// Compare the birth (creation) time of files present on both sides,
+    /// where the filesystem records it
+    #[arg(long)]
+    btime: bool,
+    /// Compare the permission bits of files and directories present on both sides
+    #[arg(long)]
+    perms: bool,
+    /// Compare the owning user and group of files and directories present on both sides
+    #[arg(long)]
+    owner: bool,
+    /// Compare user and system extended attributes of files present on both sides
+    #[arg(long)]
+    xattrs: bool,
+    /// Compare the SELinux security context of files and directories present on both sides
+    #[arg(long)]
+    selinux: bool,
+    /// Compare file capabilities (security.capability) of files present on both sides
+    #[arg(long)]
+    caps: bool,
+    /// Compare inode flags such as immutable, append-only and nocow (Linux, as shown by lsattr)
+    #[arg(long)]
+    inode_flags: bool,
+    /// Compare the Hidden, ReadOnly, System and Archive attributes (Windows only)
+    #[arg(long)]
+    win_attrs: bool,
+    /// Compare macOS resource forks, Finder info and quarantine attributes
+    #[arg(long)]
+    mac_xattrs: bool,
+    /// Compare the hardlink count of files present on both sides
+    #[arg(long)]
+    links: bool,
+    /// Compare the modification time of files present on both sides
+    /// (see --mtime-window and --mtime-precision)
+    #[arg(long)]
+    mtime: bool,
+    /// Compare the modification time of directories present on both sides
+    /// (see --mtime-window and --mtime-precision)
+    #[arg(long)]
+    dir_mtime: bool,
+    /// Compare the disk space allocated to files, e.g. to find sparse files
+    /// whose holes were filled in by a copy
+    #[arg(long)]
+    allocated: bool,
+    /// Digest algorithm for --hash
+    #[arg(long, value_enum, default_value_t = HashAlgo::Sha256)]
+    algo: HashAlgo,
+    /// Compare the roots as a whole and list every difference by its full
+    /// relative path, without grouping by direct subdirectory
+    #[arg(long)]
+    flat: bool,
+    /// List every file below a directory that exists on one side only,
+    /// instead of a single line for the directory
+    #[arg(long)]
+    expand_missing: bool,
+    /// Also list the non-empty directories missing from the other side
+    /// (empty ones always are)
+    #[arg(long, requires = "expand_missing")]
+    missing_dirs: bool,
+    /// Walk into symlinked directories instead of comparing the links
+    /// themselves; links back to an ancestor directory are skipped
+    #[arg(long)]
+    follow_symlinks: bool,
+    /// Also list files whose contents were confirmed identical
+    #[arg(long, requires = "content")]
+    show_same: bool,
+    /// Order of the files within each category (subdirectories follow the path
+    /// order unless `natural` is chosen)
+    #[arg(long, value_enum, default_value_t = SortOrder::Path)]
+    sort: SortOrder,
+    /// List at most N differences per category in each subdirectory and only
+    /// count the rest
+    #[arg(long, value_name = "N")]
+    max_diffs: Option<usize>,
+    /// With --max-diffs, stop comparing contents once N changed files were found
+    #[arg(long, requires = "max_diffs")]
+    stop_at_max: bool,
+    /// Compare only the N most recently modified files of each side, per
+    /// subdirectory, e.g. to spot-check the latest uploads
+    #[arg(long, value_name = "N")]
+    latest: Option<usize>,
+    /// Stop at the first difference and exit with status 1
+    #[arg(long)]
+    fail_fast: bool,
+    /// Report only these kinds of difference
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "KIND")]
+    only: Option<Vec<DiffKind>>,
+    /// Exit with a failure status only for these kinds of difference
+    /// (default: any)
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "KIND")]
+    fail_on: Option<Vec<DiffKind>>,
+    /// Print the time spent walking, hashing and rendering, with throughput,
+    /// to stderr at the end of the run
+    #[arg(long)]
+    timings: bool,
+    /// Do not show the --hash progress bar (shown when stderr is a terminal)
+    #[arg(long)]
+    no_progress: bool,
+    #[command(flatten)]
+    output: OutputArgs,
+    /// Log progress to stderr; repeat for more detail (-v: phases and timings,
+    /// -vv: every directory walked and file hashed)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Format of the -v log lines
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = LogFormat::Text, global = true)]
+    log_format: LogFormat,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
 
-    files
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LogFormat {
+    Text,
+    /// One JSON object per line
+    Json,
 }
 
-/// Returns the set of **direct** subdirectories (relative to `root`).
-fn direct_subdirs(root: &Path) -> HashSet<PathBuf> {
-    let mut dirs = HashSet::new();
-    if let Ok(entries) = fs::read_dir(root) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                if let Ok(rel) = path.strip_prefix(root) {
-                    dirs.insert(rel.to_path_buf());
-                }
-            }
-        }
+/// A non-negative, finite number of seconds.
+fn parse_seconds(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(secs),
+        _ => Err(format!("`{s}` is not a non-negative number of seconds")),
     }
-    dirs
 }
 
-/// Stream a file and return its SHA-256 digest.
-fn hash_file(path: &Path) -> io::Result<[u8; 32]> {
-    let mut file = fs::File::open(path)?;
-    let mut hasher = Sha256::new();
-    let mut buf = [0u8; 8192];
-
-    loop {
-        let n = file.read(&mut buf)?;
-        if n == 0 { break; }
-        hasher.update(&buf[..n]);
+/// Send `tracing` events at the level chosen by `-v` to stderr.
+fn init_logging(verbose: u8, format: LogFormat) {
+    let level = match verbose {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal());
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
     }
+}
 
-    let digest = hasher.finalize();
-    let mut out = [0u8; 32];
-    out.copy_from_slice(&digest);
-    Ok(out)
+/// Difference kinds selectable with `--only` and `--fail-on`.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum DiffKind {
+    /// Files present in DIRECTORY_B only
+    MissingLeft,
+    /// Files present in DIRECTORY_A only
+    MissingRight,
+    /// Paths that are of a different type on each side
+    TypeConflict,
+    /// Files whose contents differ
+    Changed,
+    /// Files whose compared metadata differs
+    Metadata,
+    /// Symlinks in DIRECTORY_A whose target does not exist
+    DanglingLeft,
+    /// Symlinks in DIRECTORY_B whose target does not exist
+    DanglingRight,
+    /// Files that could not be compared
+    Errors,
 }
 
-/// Returns `Ok(true)` if file contents differ. Uses size check first, then SHA-256.
-fn contents_differ(a: &Path, b: &Path) -> io::Result<bool> {
-    let ma = fs::metadata(a)?;
-    let mb = fs::metadata(b)?;
-    if ma.len() != mb.len() {
-        return Ok(true);
+impl From<DiffKind> for Category {
+    fn from(kind: DiffKind) -> Category {
+        match kind {
+            DiffKind::MissingLeft => Category::MissingInA,
+            DiffKind::MissingRight => Category::MissingInB,
+            DiffKind::TypeConflict => Category::TypeConflict,
+            DiffKind::Changed => Category::Changed,
+            DiffKind::Metadata => Category::Metadata,
+            DiffKind::DanglingLeft => Category::DanglingInA,
+            DiffKind::DanglingRight => Category::DanglingInB,
+            DiffKind::Errors => Category::Errored,
+        }
     }
-    Ok(hash_file(a)? != hash_file(b)?)
 }
 
-fn print_diff(dir_a: &Path, dir_b: &Path, check_hash: bool) {
-    let files_a = collect_files(dir_a);
-    let files_b = collect_files(dir_b);
+#[derive(Args, Debug)]
+#[command(next_help_heading = "Filters")]
+struct FilterArgs {
+    /// Compare only the files whose path relative to the roots matches GLOB,
+    /// e.g. '**/*.parquet' (repeatable)
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    include: Vec<globset::Glob>,
+    /// Skip the files and directories whose path relative to the roots
+    /// matches GLOB, e.g. '*.tmp' or '**/cache' (repeatable)
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    exclude: Vec<globset::Glob>,
+    /// Compare only the files whose path relative to the roots matches the
+    /// regular expression, e.g. '\d{8}' (repeatable; combines with --include)
+    #[arg(long, value_name = "REGEX")]
+    include_regex: Vec<regex::Regex>,
+    /// Skip the files and directories whose path relative to the roots
+    /// matches the regular expression (repeatable)
+    #[arg(long, value_name = "REGEX")]
+    exclude_regex: Vec<regex::Regex>,
+    /// Skip the files smaller than SIZE, e.g. 100M or 1GiB; a file that
+    /// crosses the limit shows up as missing on one side
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    min_size: Option<u64>,
+    /// Skip the files larger than SIZE
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_size: Option<u64>,
+    /// Compare only the files modified after WHEN: a UTC date such as
+    /// 2024-05-01 or 2024-05-01T12:00:00, or an age such as 12h, 7d or 2w
+    #[arg(long, value_name = "WHEN", value_parser = parse_time)]
+    newer_than: Option<std::time::SystemTime>,
+    /// Compare only the files modified before WHEN
+    #[arg(long, value_name = "WHEN", value_parser = parse_time)]
+    older_than: Option<std::time::SystemTime>,
+    /// Compare only the files of these types, recognized by their extension
+    /// or else their first bytes (repeatable or comma-separated)
+    #[arg(long = "type", value_enum, value_name = "TYPE", value_delimiter = ',')]
+    types: Vec<FileType>,
+    /// Descend at most N levels below the roots; 1 compares only which
+    /// subdirectories exist
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_depth: Option<u64>,
+    /// Skip the files and directories less than N levels below the roots,
+    /// e.g. 3 for only what is nested in the subdirectories' subdirectories
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    min_depth: Option<u64>,
+    /// Do not descend into directories on another file system than the
+    /// root they are below, such as /proc or network mounts
+    #[arg(long, short = 'x')]
+    one_file_system: bool,
+    /// Compare only executable files: those with an execute permission bit
+    /// on Unix, PE, ELF and Mach-O binaries and scripts elsewhere
+    #[arg(long)]
+    executables_only: bool,
+    /// Skip dotfiles and dot-directories, and on Windows the files and
+    /// directories with the hidden attribute
+    #[arg(long)]
+    skip_hidden: bool,
+    /// Also skip the usual clutter of this kind of tree (repeatable)
+    #[arg(long, value_enum)]
+    preset: Vec<Preset>,
+    /// Skip the paths ignored by the .gitignore files within each tree and
+    /// the global git excludes, as well as .git itself
+    #[arg(long)]
+    use_gitignore: bool,
+    /// Do not skip the paths listed in a .dirdiffignore file (gitignore
+    /// syntax) at the top of each tree
+    #[arg(long)]
+    no_dirdiffignore: bool,
+}
 
-    // Missing files
-    let mut missing_in_b: Vec<_> = files_a.difference(&files_b).cloned().collect();
-    missing_in_b.sort();
+#[derive(Args, Debug)]
+struct OutputArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Print nothing; report the result through the exit status only
+    #[arg(short, long, conflicts_with_all = ["output", "output_dir"])]
+    quiet: bool,
+    /// Show a diffstat-style histogram per subdirectory (same as --format stat)
+    #[arg(long)]
+    stat: bool,
+    /// List only the N largest missing or changed files, biggest first
+    #[arg(long, value_name = "N", conflicts_with = "stat")]
+    top_by_size: Option<usize>,
+    /// Print only the number of differences in each category
+    #[arg(long, conflicts_with_all = ["stat", "top_by_size", "template", "template_file"])]
+    count_only: bool,
+    /// Print only the relative path of each difference, NUL-terminated, for
+    /// `xargs -0` (combine with --only to pick categories)
+    #[arg(long, conflicts_with_all = ["stat", "top_by_size", "count_only", "template", "template_file"])]
+    print0: bool,
+    /// Layout of the differences in the text format
+    #[arg(long, value_enum, default_value_t = View::Flat)]
+    view: View,
+    /// Leave subdirectories without differences out of the text report
+    #[arg(long)]
+    hide_identical: bool,
+    /// Show both sizes and modification times on changed-file lines
+    #[arg(long)]
+    details: bool,
+    /// Show a unified diff below each changed text file, and a note for
+    /// changed binary files (with a structured --format, record which
+    /// changed files are binary)
+    #[arg(long, group = "diff_view")]
+    content_diff: bool,
+    /// In --content-diff, highlight the changed words within changed lines
+    #[arg(long, requires = "content_diff")]
+    word_diff: bool,
+    /// Show changed text files in two columns next to each other, like
+    /// diff -y
+    #[arg(long, group = "diff_view")]
+    side_by_side: bool,
+    /// Width of the --side-by-side view (default: the terminal's)
+    #[arg(long, value_name = "COLUMNS", requires = "side_by_side")]
+    width: Option<usize>,
+    /// Lines of context around each change in --content-diff and
+    /// --side-by-side
+    #[arg(short = 'U', long, value_name = "N", default_value_t = 3, requires = "diff_view")]
+    unified: usize,
+    /// When to color the text output; `auto` honors NO_COLOR
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Write the report to this file instead of stdout
+    #[arg(long, value_name = "PATH")]
+    output: Option<PathBuf>,
+    /// Compress the report on the fly
+    #[arg(long, value_enum, value_name = "ALGORITHM")]
+    compress: Option<Compression>,
+    /// Write one report file per subdirectory into this directory, plus an index
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    output_dir: Option<PathBuf>,
+    /// Print one line per difference using this template instead of --format,
+    /// e.g. '{category}\t{path}\t{size_a}'
+    #[arg(long, value_name = "TEMPLATE", conflicts_with = "template_file")]
+    template: Option<String>,
+    /// Like --template, but read the template from a file
+    #[arg(long, value_name = "PATH")]
+    template_file: Option<PathBuf>,
+    /// Also record the run and its differences in this SQLite database
+    #[arg(long, value_name = "PATH")]
+    output_db: Option<PathBuf>,
+    /// Replace every path component with a salted hash in all output, along
+    /// with link targets, owners and error messages
+    #[arg(long, conflicts_with = "diff_view")]
+    anonymize: bool,
+    /// Salt for --anonymize; use the same salt to get the same tokens across runs
+    /// (random if not given)
+    #[arg(long, value_name = "SALT", requires = "anonymize")]
+    anonymize_salt: Option<String>,
+}
 
-    let mut missing_in_a: Vec<_> = files_b.difference(&files_a).cloned().collect();
-    missing_in_a.sort();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write a BSD mtree specification of a directory, or check a directory against one
+    Mtree {
+        /// Directory to describe or check
+        #[arg(value_name = "DIRECTORY")]
+        dir: PathBuf,
+        /// Check DIRECTORY against this specification instead of writing one
+        #[arg(long, value_name = "SPEC")]
+        verify: Option<PathBuf>,
+        /// Include SHA-256 digests when writing a specification
+        #[arg(long)]
+        hash: bool,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Write a sha256sum-compatible (or md5sum, sha1sum, ...) manifest of every file in a directory
+    Manifest {
+        /// Directory to checksum
+        #[arg(value_name = "DIRECTORY")]
+        dir: PathBuf,
+        /// Write the manifest to this file instead of stdout
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Digest algorithms of the manifest; several, comma-separated, are
+        /// computed in one read and written as tagged lines
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "sha256")]
+        algo: Vec<HashAlgo>,
+    },
+    /// Validate the completeness and fixity of a BagIt bag
+    Bagit {
+        /// Root directory of the bag
+        #[arg(value_name = "BAG")]
+        bag: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Check a directory against a sha256sum-style manifest
+    Verify {
+        /// Directory to check
+        #[arg(value_name = "DIRECTORY")]
+        dir: PathBuf,
+        /// Manifest listing the expected files and digests
+        #[arg(value_name = "MANIFEST")]
+        manifest: PathBuf,
+        /// Digest algorithm of the manifest, optionally followed by more
+        /// (comma-separated) to compute for --write-manifest in the same read
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "sha256")]
+        algo: Vec<HashAlgo>,
+        /// Also write a manifest of the files on disk, in the additional
+        /// --algo algorithms (or the manifest's if there are none)
+        #[arg(long, value_name = "PATH")]
+        write_manifest: Option<PathBuf>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Measure how fast a directory can be walked and hashed
+    Bench {
+        /// Directory to read
+        #[arg(value_name = "DIRECTORY")]
+        dir: PathBuf,
+        /// Stop hashing once this many bytes were read
+        #[arg(long, value_name = "BYTES")]
+        max_bytes: Option<u64>,
+        /// Digest algorithms to measure, computed together in one read
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "sha256")]
+        algo: Vec<HashAlgo>,
+    },
+}
 
-    // Common files (present in both) to check content equality (optional)
-    let mut changed: Vec<PathBuf> = Vec::new();
-    let mut errored: Vec<(PathBuf, String)> = Vec::new();
+/// Whether the report goes straight to an interactive terminal.
+fn writes_to_terminal(args: &OutputArgs) -> bool {
+    args.output.is_none()
+        && args.output_dir.is_none()
+        && args.compress.is_none()
+        && io::stdout().is_terminal()
+        && std::env::var_os("TERM").is_none_or(|term| term != "dumb")
+}
 
-    if check_hash {
-        let mut common: Vec<_> = files_a.intersection(&files_b).cloned().collect();
-        common.sort();
-        for rel in &common {
-            let pa = dir_a.join(rel);
-            let pb = dir_b.join(rel);
-            match contents_differ(&pa, &pb) {
-                Ok(true) => changed.push(rel.clone()),
-                Ok(false) => {},
-                Err(e) => errored.push((rel.clone(), e.to_string())),
-            }
+/// The renderer for `--quiet`, `--count-only`, `--print0`, `--template`, `--top-by-size` or `--format` (split per subdirectory with
+/// `--output-dir`), plus the database recorder if `--output-db` is given, all
+/// behind `--anonymize`.
+fn build_renderer(args: &OutputArgs) -> io::Result<Box<dyn Renderer>> {
+    let template = match (&args.template, &args.template_file) {
+        (Some(template), _) => Some(template.clone()),
+        (None, Some(path)) => Some(fs::read_to_string(path)?.trim_end_matches(['\n', '\r']).to_string()),
+        (None, None) => None,
+    };
+    let (make, extension): (Box<dyn Fn() -> Box<dyn Renderer>>, _) = match (template, args.top_by_size) {
+        _ if args.quiet => (Box::new(|| Box::new(Fanout(Vec::new()))), "txt"),
+        _ if args.count_only => (Box::new(|| Box::new(CountRenderer)), "txt"),
+        _ if args.print0 => (Box::new(|| Box::new(Print0Renderer)), "txt"),
+        (Some(template), _) => {
+            let template = TemplateRenderer::parse(&template).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            (Box::new(move || Box::new(template.clone())), "txt")
+        }
+        (None, Some(n)) => (Box::new(move || Box::new(TopRenderer::new(n))), "txt"),
+        (None, None) => {
+            let format = if args.stat { Format::Stat } else { args.format };
+            let options = Options {
+                details: args.details,
+                view: args.view,
+                hide_identical: args.hide_identical,
+                hyperlinks: writes_to_terminal(args),
+                palette: Palette::new(args.color.enabled(writes_to_terminal(args))),
+                content_diff: (args.content_diff || args.side_by_side).then(|| ContentDiff {
+                    context: args.unified,
+                    words: args.word_diff,
+                    layout: if args.side_by_side { Layout::SideBySide(args.width.unwrap_or_else(terminal_width)) } else { Layout::Unified },
+                }),
+            };
+            (Box::new(move || renderer_for(format, &options)), format.extension())
         }
+    };
+    let renderer: Box<dyn Renderer> = match &args.output_dir {
+        Some(dir) => Box::new(SplitRenderer::create(dir, extension, make)?),
+        None => make(),
+    };
+    let renderer: Box<dyn Renderer> = match &args.output_db {
+        Some(db) => Box::new(Fanout(vec![renderer, Box::new(SqliteRecorder::create(db)?)])),
+        None => renderer,
+    };
+    Ok(if args.anonymize { Box::new(Anonymize::new(renderer, args.anonymize_salt.clone())) } else { renderer })
+}
+
+/// Commit the rendered report and, when it went to a file, print a short
+/// summary to stdout instead.
+fn finish_output(out: Output, report: &Report, color: ColorChoice) -> io::Result<()> {
+    let path = out.path().map(Path::to_path_buf);
+    out.commit()?;
+    if let Some(path) = path {
+        let palette = Palette::new(color.enabled(io::stdout().is_terminal()));
+        render::write_summary(&mut io::stdout().lock(), report, &path, palette)?;
     }
+    Ok(())
+}
 
-    let only_structure_equal = missing_in_a.is_empty() && missing_in_b.is_empty();
+fn run_mtree(dir: &Path, verify: Option<&Path>, hash: bool, output: &OutputArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    if !dir.is_dir() {
+        eprintln!("{} is not a valid directory.", dir.display());
+        return Ok(ExitCode::from(2));
+    }
+    let mut out = Output::open(output.output.as_deref(), output.compress)?;
 
-    if !check_hash {
-        if only_structure_equal {
-            println!("  {GREEN}✅ identical file sets (skipped content check){RESET}");
+    match verify {
+        None => {
+            mtree::write_spec(dir, &mut out, hash)?;
+            out.commit()?;
+            Ok(ExitCode::SUCCESS)
         }
-    } else if only_structure_equal && changed.is_empty() && errored.is_empty() {
-        println!("  {GREEN}✅ identical files and contents{RESET}");
-    }
-
-    if !missing_in_b.is_empty() {
-        println!(
-            "  {YELLOW}Files present in {a} but MISSING in {b}:{RESET}",
-            a = dir_a.display(),
-            b = dir_b.display()
-        );
-        for p in &missing_in_b {
-            println!("    {RED}{}{RESET}", p.display());
+        Some(spec_path) => {
+            let spec = mtree::parse_spec(&fs::read_to_string(spec_path)?)?;
+            let has_digests = spec.values().any(|e| e.digest.is_some());
+            let mut result = verify_files(dir, &spec, &[HashAlgo::Sha256], None);
+            mtree::check_metadata(dir, &spec, &mut result);
+            let mut report = Report::verification(spec_path, dir, has_digests, result);
+            report.elapsed_secs = start.elapsed().as_secs_f64();
+            render(build_renderer(output)?.as_mut(), &mut out, &report)?;
+            finish_output(out, &report, output.color)?;
+            Ok(exit_status(&report, None))
         }
     }
+}
 
-    if !missing_in_a.is_empty() {
-        println!(
-            "  {YELLOW}Files present in {b} but MISSING in {a}:{RESET}",
-            a = dir_a.display(),
-            b = dir_b.display()
-        );
-        for p in &missing_in_a {
-            println!("    {RED}{}{RESET}", p.display());
-        }
+fn run_manifest(dir: &Path, output: Option<&Path>, algos: &[HashAlgo]) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    if !dir.is_dir() {
+        eprintln!("{} is not a valid directory.", dir.display());
+        return Ok(ExitCode::from(2));
     }
+    let mut out = Output::open(output, None)?;
+    let unreadable = manifest::write_manifest(dir, &mut out, algos)?;
+    out.commit()?;
+    Ok(if unreadable > 0 { ExitCode::from(2) } else { ExitCode::SUCCESS })
+}
 
-    if check_hash && !changed.is_empty() {
-        println!("  {YELLOW}Files present in BOTH but with DIFFERENT CONTENT:{RESET}");
-        for p in &changed {
-            println!("    {RED}{}{RESET}", p.display());
+fn run_verify(
+    dir: &Path,
+    manifest_path: &Path,
+    algos: &[HashAlgo],
+    write_manifest: Option<&Path>,
+    output: &OutputArgs,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    if !dir.is_dir() {
+        eprintln!("{} is not a valid directory.", dir.display());
+        return Ok(ExitCode::from(2));
+    }
+    let expected = manifest::parse_manifest(&fs::read_to_string(manifest_path)?, algos[0])?;
+    let mut digests = BTreeMap::new();
+    let mut record = |rel: &Path, d: &[Vec<u8>]| {
+        digests.insert(rel.to_path_buf(), d.to_vec());
+    };
+    let on_digests: Option<&mut OnDigests> = write_manifest.map(|_| &mut record as _);
+    let mut report = Report::verification(manifest_path, dir, true, verify_files(dir, &expected, algos, on_digests));
+    report.algo = algos[0];
+
+    if let Some(path) = write_manifest {
+        // The first algorithm is the old manifest's; write the others.
+        let skip = usize::from(algos.len() > 1);
+        let mut out = Output::open(Some(path), None)?;
+        manifest::write_header(&mut out, &algos[skip..])?;
+        for (rel, d) in &digests {
+            manifest::write_entry(&mut out, rel, &algos[skip..], &d[skip..])?;
         }
+        out.commit()?;
     }
+    report.elapsed_secs = start.elapsed().as_secs_f64();
 
-    if check_hash && !errored.is_empty() {
-        println!("  {YELLOW}Files that could not be compared (errors):{RESET}");
-        for (p, e) in &errored {
-            println!("    {RED}{} — {}{RESET}", p.display(), e);
+    let mut out = Output::open(output.output.as_deref(), output.compress)?;
+    render(build_renderer(output)?.as_mut(), &mut out, &report)?;
+    finish_output(out, &report, output.color)?;
+    Ok(exit_status(&report, None))
+}
+
+fn run_bagit(bag: &Path, output: &OutputArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    if !bag.is_dir() {
+        eprintln!("{} is not a valid directory.", bag.display());
+        return Ok(ExitCode::from(2));
+    }
+    let result = bagit::validate(bag)?;
+    let mut report = Report::verification(&bag.join(bagit::PAYLOAD_MANIFEST), bag, true, result);
+    report.elapsed_secs = start.elapsed().as_secs_f64();
+
+    let mut out = Output::open(output.output.as_deref(), output.compress)?;
+    render(build_renderer(output)?.as_mut(), &mut out, &report)?;
+    finish_output(out, &report, output.color)?;
+    Ok(exit_status(&report, None))
+}
+
+/// Walk `dir`, then hash its files (up to `max_bytes`), and print the rate
+/// of each phase.
+fn run_bench(dir: &Path, max_bytes: Option<u64>, algos: &[HashAlgo]) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    if !dir.is_dir() {
+        eprintln!("{} is not a valid directory.", dir.display());
+        return Ok(ExitCode::from(2));
+    }
+    let walk_start = Instant::now();
+    let mut files: Vec<_> = collect_regular_files(dir).into_iter().collect();
+    let walk_secs = walk_start.elapsed().as_secs_f64();
+    files.sort();
+    println!("walk  {:>8.2}s  {} files, {:.0} files/s", walk_secs, files.len(), files.len() as f64 / walk_secs.max(f64::EPSILON));
+
+    let hash_start = Instant::now();
+    let (mut hashed, mut bytes) = (0usize, 0u64);
+    for rel in &files {
+        if max_bytes.is_some_and(|max| bytes >= max) {
+            break;
+        }
+        let path = dir.join(rel);
+        if hash_file_multi(&path, algos).is_ok() {
+            hashed += 1;
+            bytes += fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
         }
     }
+    let hash_secs = hash_start.elapsed().as_secs_f64();
+    let names: Vec<_> = algos.iter().map(|a| a.as_str()).collect();
+    println!("hash  {:>8.2}s  {hashed} files, {}, {} ({})", hash_secs, render::human_bytes(bytes), render::human_rate(bytes, hash_secs), names.join("+"));
+    Ok(ExitCode::SUCCESS)
 }
 
-#[derive(Parser, Debug)]
-#[command(name = "dir_compare", version, about = "Compare directory structures (and optionally contents) by subdirectory.")]
-struct Cli {
-    /// First directory to compare
-    #[arg(value_name = "DIRECTORY_A")]
-    dir_a: PathBuf,
-    /// Second directory to compare
-    #[arg(value_name = "DIRECTORY_B")]
-    dir_b: PathBuf,
-    /// Also compare file contents using SHA-256
-    #[arg(long)]
-    hash: bool,
+/// Per-phase wall-clock times for `--timings`. Entries streamed while
+/// comparing are rendered during the walk and hash phases; `render` covers
+/// the final output only.
+fn write_timings(out: &mut dyn io::Write, report: &Report, render_secs: f64) -> io::Result<()> {
+    let stats = report.stats();
+    let files = stats.files_a + stats.files_b;
+    let files_per_sec = if stats.walk_secs > 0.0 { files as f64 / stats.walk_secs } else { 0.0 };
+    writeln!(out, "Timings:")?;
+    writeln!(out, "  walk    {:>8.2}s  {files} files, {files_per_sec:.0} files/s", stats.walk_secs)?;
+    if report.hash {
+        let rate = render::human_rate(stats.bytes_hashed, stats.hash_secs);
+        writeln!(out, "  hash    {:>8.2}s  {}, {rate}", stats.hash_secs, render::human_bytes(stats.bytes_hashed))?;
+    }
+    writeln!(out, "  render  {render_secs:>8.2}s")?;
+    writeln!(out, "  total   {:>8.2}s", report.elapsed_secs + render_secs)
+}
+
+/// diff-style exit status: 0 when identical, 1 when something differs and 2
+/// when files could not be compared. With `fail_on`, only differences of
+/// those categories count.
+fn exit_status(report: &Report, fail_on: Option<&[Category]>) -> ExitCode {
+    let fails = |category| fail_on.is_none_or(|f| f.contains(&category)) && report.differs_in(category);
+    if fails(Category::Errored) {
+        ExitCode::from(2)
+    } else if Category::ALL.into_iter().any(fails) {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("dir_compare: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let start = Instant::now();
     let cli = Cli::parse();
+    init_logging(cli.verbose, cli.log_format);
+
+    if let Some(command) = &cli.command {
+        return match command {
+            Command::Mtree { dir, verify, hash, output } => run_mtree(dir, verify.as_deref(), *hash, output),
+            Command::Manifest { dir, output, algo } => run_manifest(dir, output.as_deref(), algo),
+            Command::Bagit { bag, output } => run_bagit(bag, output),
+            Command::Verify { dir, manifest, algo, write_manifest, output } => {
+                run_verify(dir, manifest, algo, write_manifest.as_deref(), output)
+            }
+            Command::Bench { dir, max_bytes, algo } => run_bench(dir, *max_bytes, algo),
+        };
+    }
 
-    let dir_a = cli.dir_a;
-    let dir_b = cli.dir_b;
-    let check_hash = cli.hash;
+    // Both are `required` unless a subcommand was given.
+    let dir_a = cli.dir_a.expect("DIRECTORY_A is required");
+    let dir_b = cli.dir_b.expect("DIRECTORY_B is required");
+    let mut excludes = cli.filter.exclude.clone();
+    excludes.extend(cli.filter.preset.iter().flat_map(|preset| preset.globs()));
+    let mut options = CompareOptions {
+        check_hash: cli.hash || cli.bytes || cli.quick.is_some() || cli.size_only,
+        content: match (cli.bytes, cli.quick, cli.size_only) {
+            (true, _, _) => ContentCheck::Bytes,
+            (_, Some(_), _) => ContentCheck::Quick,
+            (_, _, true) => ContentCheck::SizeOnly,
+            _ => ContentCheck::Hash,
+        },
+        algo: cli.algo,
+        sample: cli.quick.unwrap_or(0).saturating_mul(1024),
+        trust_mtime: cli.trust_mtime,
+        hash_max_size: cli.hash_max_size,
+        ignore_lines: (!cli.ignore_matching_lines.is_empty())
+            .then(|| regex::bytes::RegexSet::new(cli.ignore_matching_lines.iter().map(|r| r.as_str())).expect("already parsed")),
+        mtime: MtimeMatch { window: Duration::from_secs_f64(cli.mtime_window), precision: cli.mtime_precision },
+        metadata: MetadataChecks { btime: cli.btime, perms: cli.perms, owner: cli.owner, xattrs: cli.xattrs, selinux: cli.selinux, caps: cli.caps, inode_flags: cli.inode_flags, win_attrs: cli.win_attrs, mac_xattrs: cli.mac_xattrs, links: cli.links, mtime: cli.mtime, dir_mtime: cli.dir_mtime, allocated: cli.allocated },
+        expand_missing: cli.expand_missing,
+        missing_dirs: cli.missing_dirs,
+        follow_symlinks: cli.follow_symlinks,
+        filter: Filter::default().include(&cli.filter.include).exclude(&excludes)
+            .include_regex(&cli.filter.include_regex).exclude_regex(&cli.filter.exclude_regex)
+            .size(cli.filter.min_size, cli.filter.max_size)
+            .modified(cli.filter.newer_than, cli.filter.older_than)
+            .skip_hidden(cli.filter.skip_hidden).types(&cli.filter.types)
+            .executables_only(cli.filter.executables_only)
+            .max_depth(cli.filter.max_depth.map(|n| n as usize))
+            .min_depth(cli.filter.min_depth.map(|n| n as usize))
+            .one_file_system(cli.filter.one_file_system)
+            .gitignore(cli.filter.use_gitignore)
+            .dirdiffignore(!cli.filter.no_dirdiffignore),
+        show_same: cli.show_same,
+        only: cli.only.map(|kinds| kinds.into_iter().map(Category::from).collect()),
+        sort: cli.sort,
+        max_diffs: cli.max_diffs,
+        stop_at_max: cli.stop_at_max,
+        latest: cli.latest,
+        detect_binary: cli.output.content_diff || cli.output.side_by_side,
+        fail_fast: cli.fail_fast,
+        progress: None,
+    };
 
     if !dir_a.is_dir() || !dir_b.is_dir() {
         eprintln!("Both arguments must be valid directories.");
-        std::process::exit(1);
+        return Ok(ExitCode::from(2));
     }
 
-    // Gather ALL unique direct subdirectories from both sides
-    let all_subdirs: HashSet<PathBuf> = direct_subdirs(&dir_a)
-        .union(&direct_subdirs(&dir_b))
-        .cloned()
-        .collect();
-
-    // NOTE: we no longer include the root – user asked to skip it
+    let subdirs = if cli.flat {
+        // The empty path compares the roots themselves, including their files.
+        vec![PathBuf::new()]
+    } else {
+        // Gather ALL unique direct subdirectories from both sides
+        let all_subdirs: HashSet<PathBuf> = direct_subdirs(&dir_a)
+            .union(&direct_subdirs(&dir_b))
+            .cloned()
+            .collect();
+
+        // NOTE: we no longer include the root – user asked to skip it
+
+        // Sort for deterministic order
+        let mut subdirs: Vec<_> = all_subdirs.into_iter().filter(|sub| options.filter.walks_subdir(&dir_a, sub) && options.filter.walks_subdir(&dir_b, sub)).collect();
+        subdirs.sort_by(|x, y| options.sort.cmp_paths(x, y));
+        subdirs
+    };
+
+    // The bar would garble -v log lines, so it is only shown without them.
+    if options.check_hash && options.content != ContentCheck::SizeOnly && !cli.no_progress && !cli.output.quiet && cli.verbose == 0 && io::stderr().is_terminal() {
+        let (files, bytes) = subdirs
+            .iter()
+            .map(|sub| common_files(&dir_a.join(sub), &dir_b.join(sub), options.follow_symlinks, &options.filter.under(sub)))
+            .fold((0, 0), |(f, b), (sf, sb)| (f + sf, b + sb));
+        options.progress = Some(Progress::new(files, bytes));
+    }
 
-    // Sort for deterministic order
-    let mut subdirs: Vec<_> = all_subdirs.into_iter().collect();
-    subdirs.sort();
+    let mut out = Output::open(cli.output.output.as_deref(), cli.output.compress)?;
+    let mut renderer = build_renderer(&cli.output)?;
+    let mut report = Report::new(dir_a, dir_b, options.check_hash);
+    report.content = options.content;
+    report.algo = options.algo;
+    report.mtime = options.mtime;
 
+    info!(subdirs = subdirs.len(), "comparing");
     for sub in &subdirs {
-        let path_a = dir_a.join(sub);
-        let path_b = dir_b.join(sub);
-        let label = sub.display();
-
-        println!("\n{CYAN}=== Subdirectory: {} ==={RESET}", label);
-
-        match (path_a.is_dir(), path_b.is_dir()) {
-            (true, true) => print_diff(&path_a, &path_b, check_hash),
-            (true, false) => println!("  {RED}Present in {} but MISSING entirely in {}{RESET}", dir_a.display(), dir_b.display()),
-            (false, true) => println!("  {RED}Present in {} but MISSING entirely in {}{RESET}", dir_b.display(), dir_a.display()),
-            _ => (),
+        info!(subdir = %sub.display(), "comparing subdirectory");
+        let sub_report = if cli.flat {
+            // Hold the entries back so they can be emitted in global path order.
+            let mut sub_report = compare_subdir(&report.dir_a, &report.dir_b, sub, &options, &mut |_| Ok(()))?;
+            sub_report.entries.sort_by(|x, y| options.sort.cmp_entries(x, y));
+            for entry in &sub_report.entries {
+                renderer.entry(&mut out, &report, sub, entry)?;
+            }
+            sub_report
+        } else {
+            let mut on_entry = |entry: &DiffEntry| renderer.entry(&mut out, &report, sub, entry);
+            compare_subdir(&report.dir_a, &report.dir_b, sub, &options, &mut on_entry)?
+        };
+        // A subdirectory missing on one side counts as missing files.
+        let skip = match sub_report.status {
+            SubdirStatus::OnlyInA => !options.reports(Category::MissingInB),
+            SubdirStatus::OnlyInB => !options.reports(Category::MissingInA),
+            SubdirStatus::Compared => false,
+        };
+        if skip {
+            continue;
+        }
+        renderer.subdir(&mut out, &report, &sub_report)?;
+        let differs = !sub_report.is_identical();
+        report.subdirs.push(sub_report);
+        if options.fail_fast && differs {
+            break;
         }
     }
 
-    Ok(())
+    if let Some(progress) = &options.progress {
+        progress.finish();
+    }
+    report.elapsed_secs = start.elapsed().as_secs_f64();
+    info!(elapsed_secs = report.elapsed_secs, "comparison finished");
+    let render_start = Instant::now();
+    renderer.finish(&mut out, &report)?;
+    finish_output(out, &report, cli.output.color)?;
+    if cli.timings {
+        write_timings(&mut io::stderr().lock(), &report, render_start.elapsed().as_secs_f64())?;
+    }
+
+    let fail_on: Option<Vec<Category>> = cli.fail_on.map(|kinds| kinds.into_iter().map(Category::from).collect());
+    Ok(exit_status(&report, fail_on.as_deref()))
 }
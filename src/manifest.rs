@@ -0,0 +1,129 @@
+//! Checksum manifests compatible with `sha256sum`, `b3sum` and `xxhsum`.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use tracing::warn;
+
+use crate::compare::{collect_regular_files, hash_file_multi, to_hex, Expected, HashAlgo};
+
+/// Escape a path the way coreutils does: names containing a backslash or a
+/// newline get those escaped and the whole line prefixed with `\`.
+fn escape_name(name: &str) -> (bool, String) {
+    if !name.contains(['\\', '\n', '\r']) {
+        return (false, name.to_string());
+    }
+    let escaped = name.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r");
+    (true, escaped)
+}
+
+/// Write a `sha256sum`-format manifest (`<hex>  <relative path>`) of every
+/// regular file below `root`, sorted by path, with `algo` digests. With more
+/// than one algorithm, each file gets a BSD tagged line (`TAG (path) = hex`)
+/// per algorithm, all computed from a single read.
+///
+/// Files that cannot be read are left out with a warning; returns how many.
+pub fn write_manifest(root: &Path, out: &mut dyn Write, algos: &[HashAlgo]) -> io::Result<usize> {
+    let mut files: Vec<_> = collect_regular_files(root).into_iter().collect();
+    files.sort();
+    write_header(out, algos)?;
+    let mut unreadable = 0;
+    for rel in files {
+        match hash_file_multi(&root.join(&rel), algos) {
+            Ok(digests) => write_entry(out, &rel, algos, &digests)?,
+            Err(e) => {
+                warn!(file = %rel.display(), error = %e, "could not read, left out of the manifest");
+                unreadable += 1;
+            }
+        }
+    }
+    Ok(unreadable)
+}
+
+/// Comment lines warning about weak `algos`, ignored when parsing.
+pub fn write_header(out: &mut dyn Write, algos: &[HashAlgo]) -> io::Result<()> {
+    for warning in algos.iter().filter_map(|a| a.collision_warning()) {
+        writeln!(out, "# {warning}")?;
+    }
+    Ok(())
+}
+
+/// The manifest line(s) for `rel`, whose `algos` digests are `digests`.
+pub fn write_entry(out: &mut dyn Write, rel: &Path, algos: &[HashAlgo], digests: &[Vec<u8>]) -> io::Result<()> {
+    // sha256sum always uses forward slashes, regardless of platform.
+    let name = rel.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+    let (escaped, name) = escape_name(&name);
+    let prefix = if escaped { "\\" } else { "" };
+    if let [_] = algos {
+        return writeln!(out, "{prefix}{}  {name}", to_hex(&digests[0]));
+    }
+    for (algo, digest) in algos.iter().zip(digests) {
+        writeln!(out, "{prefix}{} ({name}) = {}", algo.tag(), to_hex(digest))?;
+    }
+    Ok(())
+}
+
+/// Undo [`escape_name`].
+fn unescape_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn invalid(lineno: usize, msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {msg}", lineno + 1))
+}
+
+/// Parse a manifest of `algo` digests in either the GNU (`<hex>  <path>`,
+/// optionally with a `*` binary marker) or the BSD tagged
+/// (`SHA256 (<path>) = <hex>`) format. Tagged lines of other algorithms are
+/// skipped.
+pub fn parse_manifest(text: &str, algo: HashAlgo) -> io::Result<BTreeMap<PathBuf, Expected>> {
+    let mut files = BTreeMap::new();
+    let tag = format!("{} (", algo.tag());
+    let other_tags: Vec<String> = HashAlgo::value_variants().iter().filter(|&&a| a != algo).map(|a| format!("{} (", a.tag())).collect();
+    for (lineno, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (escaped, line) = match line.strip_prefix('\\') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        if other_tags.iter().any(|t| line.starts_with(t.as_str())) {
+            continue;
+        }
+
+        let (digest, name) = if let Some(tagged) = line.strip_prefix(tag.as_str()) {
+            let (name, digest) = tagged.rsplit_once(") = ").ok_or_else(|| invalid(lineno, "malformed tagged line"))?;
+            (digest, name)
+        } else {
+            let (digest, rest) = line.split_once(' ').ok_or_else(|| invalid(lineno, "missing file name"))?;
+            // Text mode has a second space, binary mode a `*`.
+            let name = rest.strip_prefix([' ', '*']).ok_or_else(|| invalid(lineno, "malformed separator"))?;
+            (digest, name)
+        };
+
+        if digest.len() != algo.hex_len() || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid(lineno, &format!("not a {} digest", algo.as_str())));
+        }
+        let name = if escaped { unescape_name(name) } else { name.to_string() };
+        let rel: PathBuf = Path::new(&name).components().filter(|c| c.as_os_str() != ".").collect();
+        files.insert(rel, Expected { size: None, digest: Some(digest.to_ascii_lowercase()), ..Expected::default() });
+    }
+    Ok(files)
+}
@@ -0,0 +1,370 @@
+//! Comparison of file metadata other than size and contents.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::render::human_bytes;
+use crate::report::{rfc3339, AttrDiff};
+
+/// Which metadata attributes of paths present on both sides are compared.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetadataChecks {
+    /// Birth (creation) time, where the platform and filesystem record it.
+    pub btime: bool,
+    /// Unix permission bits (the read-only flag elsewhere), of directories too.
+    pub perms: bool,
+    /// Owning uid and gid (Unix only), of directories too.
+    pub owner: bool,
+    /// Extended attributes in the `user` and `system` namespaces.
+    pub xattrs: bool,
+    /// SELinux security context, of directories too.
+    pub selinux: bool,
+    /// File capabilities (`security.capability`).
+    pub caps: bool,
+    /// ext4/btrfs inode flags as shown by `lsattr` (Linux only), of directories too.
+    pub inode_flags: bool,
+    /// Hidden/ReadOnly/System/Archive attributes (Windows only).
+    pub win_attrs: bool,
+    /// macOS resource fork, Finder info and quarantine attributes.
+    pub mac_xattrs: bool,
+    /// Hardlink count (Unix only).
+    pub links: bool,
+    /// Modification time of files, within the comparison's mtime window and
+    /// precision.
+    pub mtime: bool,
+    /// Modification time of directories, likewise.
+    pub dir_mtime: bool,
+    /// Disk space allocated to files (Unix only), which differs between a
+    /// sparse file and a copy that filled in its holes.
+    pub allocated: bool,
+}
+
+impl MetadataChecks {
+    /// Whether any attribute is compared at all.
+    pub fn any(self) -> bool {
+        self.btime || self.perms || self.owner || self.xattrs || self.selinux || self.caps || self.inode_flags || self.win_attrs || self.mac_xattrs || self.links || self.mtime || self.allocated
+    }
+
+    /// Whether directories present on both sides are compared as well.
+    pub fn covers_dirs(self) -> bool {
+        self.perms || self.owner || self.selinux || self.inode_flags || self.dir_mtime
+    }
+
+    /// The selected attributes that differ between `a` and `b`.
+    pub fn compare(self, a: &Path, b: &Path) -> io::Result<Vec<AttrDiff>> {
+        if !self.any() {
+            return Ok(Vec::new());
+        }
+        let (meta_a, meta_b) = (fs::metadata(a)?, fs::metadata(b)?);
+        let mut diffs = Vec::new();
+        if self.btime {
+            // Only comparable when both filesystems record it (statx on Linux).
+            if let (Ok(x), Ok(y)) = (meta_a.created(), meta_b.created()) {
+                if x != y {
+                    diffs.push(AttrDiff::new("btime", Some(rfc3339(x)), Some(rfc3339(y))));
+                }
+            }
+        }
+        if self.perms {
+            let (x, y) = (mode(&meta_a), mode(&meta_b));
+            if x != y {
+                diffs.push(AttrDiff::new("mode", Some(x), Some(y)));
+            }
+        }
+        #[cfg(unix)]
+        if self.owner {
+            use std::os::unix::fs::MetadataExt;
+            if meta_a.uid() != meta_b.uid() {
+                let names = names("/etc/passwd");
+                diffs.push(AttrDiff::new("owner", Some(id(meta_a.uid(), names)), Some(id(meta_b.uid(), names))));
+            }
+            if meta_a.gid() != meta_b.gid() {
+                let names = names("/etc/group");
+                diffs.push(AttrDiff::new("group", Some(id(meta_a.gid(), names)), Some(id(meta_b.gid(), names))));
+            }
+        }
+        if self.xattrs {
+            // Namespaces are a Linux notion; elsewhere every attribute counts.
+            let user = |name: &str| {
+                !cfg!(target_os = "linux") || name.starts_with("user.") || name.starts_with("system.")
+            };
+            xattr_diffs(a, b, user, &mut diffs)?;
+        }
+        if self.selinux {
+            xattr_attr(a, b, "security.selinux", "selinux", xattr_value, &mut diffs)?;
+        }
+        if self.caps {
+            xattr_attr(a, b, "security.capability", "caps", caps_value, &mut diffs)?;
+        }
+        if self.mac_xattrs {
+            for (name, label) in MAC_XATTRS {
+                xattr_attr(a, b, name, label, xattr_value, &mut diffs)?;
+            }
+        }
+        // Opening a device node to query them could have side effects.
+        if self.inode_flags && (meta_a.is_file() || meta_a.is_dir()) && (meta_b.is_file() || meta_b.is_dir()) {
+            if let (Some(x), Some(y)) = (inode_flags(a)?, inode_flags(b)?) {
+                if x != y {
+                    diffs.push(AttrDiff::new("flags", Some(flag_names(x)), Some(flag_names(y))));
+                }
+            }
+        }
+        #[cfg(unix)]
+        if self.links {
+            use std::os::unix::fs::MetadataExt;
+            if meta_a.nlink() != meta_b.nlink() {
+                diffs.push(AttrDiff::new("links", Some(meta_a.nlink().to_string()), Some(meta_b.nlink().to_string())));
+            }
+        }
+        #[cfg(unix)]
+        if self.allocated && meta_a.is_file() {
+            use std::os::unix::fs::MetadataExt;
+            let (x, y) = (meta_a.blocks() * 512, meta_b.blocks() * 512);
+            if x != y {
+                diffs.push(AttrDiff::new("allocated", Some(human_bytes(x)), Some(human_bytes(y))));
+            }
+        }
+        #[cfg(windows)]
+        if self.win_attrs {
+            use std::os::windows::fs::MetadataExt;
+            let (x, y) = (win_attr_names(meta_a.file_attributes()), win_attr_names(meta_b.file_attributes()));
+            if x != y {
+                diffs.push(AttrDiff::new("attributes", Some(x), Some(y)));
+            }
+        }
+        Ok(diffs)
+    }
+}
+
+/// Pushes an `xattr:<name>` entry for each attribute accepted by `select`
+/// that is missing on one side or has a different value.
+fn xattr_diffs(a: &Path, b: &Path, select: impl Fn(&str) -> bool, diffs: &mut Vec<AttrDiff>) -> io::Result<()> {
+    let (names_a, names_b) = (xattr_names(a, &select)?, xattr_names(b, &select)?);
+    for name in names_a.union(&names_b) {
+        let (x, y) = (xattr::get(a, name)?, xattr::get(b, name)?);
+        if x != y {
+            diffs.push(AttrDiff::new(format!("xattr:{name}"), x.as_deref().map(xattr_value), y.as_deref().map(xattr_value)));
+        }
+    }
+    Ok(())
+}
+
+/// Legacy macOS metadata kept in xattrs, with the labels they are reported by.
+const MAC_XATTRS: [(&str, &str); 3] = [
+    ("com.apple.ResourceFork", "resource-fork"),
+    ("com.apple.FinderInfo", "finder-info"),
+    ("com.apple.quarantine", "quarantine"),
+];
+
+/// Pushes `label` when the single attribute `name` differs, showing the
+/// values with `show`.
+fn xattr_attr(
+    a: &Path,
+    b: &Path,
+    name: &str,
+    label: &str,
+    show: fn(&[u8]) -> String,
+    diffs: &mut Vec<AttrDiff>,
+) -> io::Result<()> {
+    let (x, y) = (xattr_get(a, name)?, xattr_get(b, name)?);
+    if x != y {
+        diffs.push(AttrDiff::new(label, x.as_deref().map(show), y.as_deref().map(show)));
+    }
+    Ok(())
+}
+
+/// Value of attribute `name`; none where the filesystem does not support them.
+fn xattr_get(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    match xattr::get(path, name) {
+        Err(e) if unsupported(&e) => Ok(None),
+        result => result,
+    }
+}
+
+/// Attribute names of `path` accepted by `select`; none where the filesystem
+/// does not support them.
+fn xattr_names(path: &Path, select: impl Fn(&str) -> bool) -> io::Result<BTreeSet<String>> {
+    match xattr::list(path) {
+        Ok(names) => Ok(names.filter_map(|n| n.into_string().ok()).filter(|n| select(n)).collect()),
+        Err(e) if unsupported(&e) => Ok(BTreeSet::new()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(unix)]
+fn unsupported(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(libc::ENOTSUP)
+}
+
+#[cfg(not(unix))]
+fn unsupported(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::Unsupported
+}
+
+/// Short printable text as is, anything else by its size.
+fn xattr_value(value: &[u8]) -> String {
+    let text = std::str::from_utf8(value).ok().map(|t| t.trim_end_matches('\0'));
+    match text {
+        Some(t) if t.len() <= 64 && t.chars().all(|c| !c.is_control()) => format!("{t:?}"),
+        _ => format!("{} bytes", value.len()),
+    }
+}
+
+/// A numeric id with its name appended when known, e.g. `1000 (alice)`.
+#[cfg(unix)]
+fn id(id: u32, names: &HashMap<u32, String>) -> String {
+    match names.get(&id) {
+        Some(name) => format!("{id} ({name})"),
+        None => id.to_string(),
+    }
+}
+
+/// Id-to-name table of a `name:x:id:...` database, read once per file.
+/// Accounts known only to NSS services (LDAP, ...) stay numeric.
+#[cfg(unix)]
+fn names(file: &'static str) -> &'static HashMap<u32, String> {
+    static PASSWD: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    static GROUP: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    let cell = if file == "/etc/group" { &GROUP } else { &PASSWD };
+    cell.get_or_init(|| {
+        let text = fs::read_to_string(file).unwrap_or_default();
+        text.lines()
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let id = fields.nth(1)?.parse().ok()?;
+                Some((id, name.to_string()))
+            })
+            .collect()
+    })
+}
+
+/// Permission bits in octal, e.g. `0644`.
+#[cfg(unix)]
+fn mode(meta: &fs::Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+    format!("{:04o}", meta.mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode(meta: &fs::Metadata) -> String {
+    if meta.permissions().readonly() { "read-only" } else { "writable" }.to_string()
+}
+
+/// Capability names by bit number, as in `<linux/capability.h>`.
+const CAPS: [&str; 41] = [
+    "chown", "dac_override", "dac_read_search", "fowner", "fsetid", "kill", "setgid", "setuid", "setpcap",
+    "linux_immutable", "net_bind_service", "net_broadcast", "net_admin", "net_raw", "ipc_lock", "ipc_owner",
+    "sys_module", "sys_rawio", "sys_chroot", "sys_ptrace", "sys_pacct", "sys_admin", "sys_boot", "sys_nice",
+    "sys_resource", "sys_time", "sys_tty_config", "mknod", "lease", "audit_write", "audit_control", "setfcap",
+    "mac_override", "mac_admin", "syslog", "wake_alarm", "block_suspend", "audit_read", "perfmon", "bpf",
+    "checkpoint_restore",
+];
+
+/// A `vfs_cap_data` value in `getcap` notation, e.g. `cap_net_bind_service=ep`.
+fn caps_value(value: &[u8]) -> String {
+    let word = |i: usize| value.get(i * 4..i * 4 + 4).map(|w| u32::from_le_bytes(w.try_into().unwrap()));
+    let Some(magic) = word(0) else {
+        return xattr_value(value);
+    };
+    // v1 carries one 32-bit set, v2 and v3 two; words alternate permitted and inheritable.
+    let halves = if magic >> 24 == 1 { 1 } else { 2 };
+    let (mut permitted, mut inheritable) = (0u64, 0u64);
+    for half in 0..halves {
+        let (Some(p), Some(i)) = (word(1 + half * 2), word(2 + half * 2)) else {
+            return xattr_value(value);
+        };
+        permitted |= (p as u64) << (32 * half);
+        inheritable |= (i as u64) << (32 * half);
+    }
+    let effective = magic & 1 != 0;
+    // Group capabilities by their flags, like getcap does.
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for bit in 0..64 {
+        let (p, i) = (permitted >> bit & 1 != 0, inheritable >> bit & 1 != 0);
+        if !p && !i {
+            continue;
+        }
+        let flags = format!("{}{}{}", if effective { "e" } else { "" }, if i { "i" } else { "" }, if p { "p" } else { "" });
+        let name = CAPS.get(bit).map_or_else(|| format!("cap_{bit}"), |n| format!("cap_{n}"));
+        match groups.iter_mut().find(|(f, _)| *f == flags) {
+            Some((_, names)) => names.push(name),
+            None => groups.push((flags, vec![name])),
+        }
+    }
+    if groups.is_empty() {
+        return "none".to_string();
+    }
+    groups.iter().map(|(flags, names)| format!("{}={flags}", names.join(","))).collect::<Vec<_>>().join(" ")
+}
+
+/// `FILE_ATTRIBUTE_*` bits compared by `--win-attrs`, as `attrib` shows them.
+#[cfg(windows)]
+fn win_attr_names(attributes: u32) -> String {
+    const ATTRIBUTES: [(u32, &str); 4] = [(0x1, "R"), (0x2, "H"), (0x4, "S"), (0x20, "A")];
+    let names: String = ATTRIBUTES.iter().filter(|(bit, _)| attributes & bit != 0).map(|(_, name)| *name).collect();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names
+    }
+}
+
+/// User-settable inode flags and their `chattr` names; filesystem-managed
+/// ones (extents, inline data, ...) differ between copies for no reason.
+const INODE_FLAGS: [(u32, &str); 14] = [
+    (0x1, "secrm"),
+    (0x2, "undelete"),
+    (0x4, "compress"),
+    (0x8, "sync"),
+    (0x10, "immutable"),
+    (0x20, "append"),
+    (0x40, "nodump"),
+    (0x80, "noatime"),
+    (0x400, "nocompress"),
+    (0x4000, "journal"),
+    (0x8000, "notail"),
+    (0x10000, "dirsync"),
+    (0x20000, "topdir"),
+    (0x800000, "nocow"),
+];
+
+/// The selected flags, comma separated, or `none`.
+fn flag_names(flags: u32) -> String {
+    let names: Vec<_> = INODE_FLAGS.iter().filter(|(bit, _)| flags & bit != 0).map(|(_, name)| *name).collect();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(",")
+    }
+}
+
+/// The user-settable inode flags of `path`; none where the filesystem has no
+/// such flags.
+#[cfg(target_os = "linux")]
+fn inode_flags(path: &Path) -> io::Result<Option<u32>> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let file = fs::OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path)?;
+    let mut flags: libc::c_int = 0;
+    // SAFETY: FS_IOC_GETFLAGS writes a single int through the pointer, which
+    // outlives the call; the descriptor is open for its duration.
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } == -1 {
+        let e = io::Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::ENOTTY | libc::ENOTSUP | libc::EINVAL) => Ok(None),
+            _ => Err(e),
+        };
+    }
+    let mask = INODE_FLAGS.iter().fold(0, |mask, (bit, _)| mask | bit);
+    Ok(Some(flags as u32 & mask))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn inode_flags(_path: &Path) -> io::Result<Option<u32>> {
+    Ok(None)
+}
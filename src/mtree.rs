@@ -0,0 +1,329 @@
+//! BSD mtree(5) specifications: writing one for a tree and checking a tree
+//! against an existing one.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::compare::{hash_file, to_hex, Expected, HashAlgo};
+use crate::report::{AttrDiff, Category, DiffEntry, SubdirReport};
+
+/// Encode a file name the way mtree(5) expects (strvis-style octal escapes).
+fn encode(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for b in name.bytes() {
+        match b {
+            b'\\' => out.push_str("\\\\"),
+            b'!'..=b'~' if b != b'#' => out.push(b as char),
+            _ => out.push_str(&format!("\\{b:03o}")),
+        }
+    }
+    out
+}
+
+/// Decode an mtree(5) file name.
+fn decode(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 1 < bytes.len() {
+            let rest = &bytes[i + 1..];
+            if rest.len() >= 3 && rest[..3].iter().all(|c| (b'0'..=b'7').contains(c)) {
+                let value = (rest[0] - b'0') as u32 * 64 + (rest[1] - b'0') as u32 * 8 + (rest[2] - b'0') as u32;
+                out.push(value as u8);
+                i += 4;
+                continue;
+            }
+            let escaped = match rest[0] {
+                b's' => b' ',
+                b't' => b'\t',
+                b'n' => b'\n',
+                b'r' => b'\r',
+                other => other,
+            };
+            out.push(escaped);
+            i += 2;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn spec_name(rel: &Path) -> String {
+    let mut name = String::from(".");
+    for component in rel.components() {
+        name.push('/');
+        name.push_str(&encode(&component.as_os_str().to_string_lossy()));
+    }
+    name
+}
+
+/// The `mode`, `uid`, `gid` and `time` keywords of a file with `meta`, as far
+/// as this platform has them.
+fn metadata_keywords(meta: &fs::Metadata) -> Vec<(&'static str, String)> {
+    let mut kw = Vec::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        kw.push(("mode", format!("{:04o}", meta.mode() & 0o7777)));
+        kw.push(("uid", meta.uid().to_string()));
+        kw.push(("gid", meta.gid().to_string()));
+    }
+    if let Ok(time) = meta.modified().map(|t| t.duration_since(UNIX_EPOCH).unwrap_or_default()) {
+        kw.push(("time", format!("{}.{:09}", time.as_secs(), time.subsec_nanos())));
+    }
+    kw
+}
+
+fn keywords(meta: &fs::Metadata) -> String {
+    metadata_keywords(meta).into_iter().map(|(k, v)| format!(" {k}={v}")).collect()
+}
+
+/// `value` of the metadata `keyword` in the form [`metadata_keywords`] gives,
+/// or `None` if it is not a valid value.
+fn normalize(keyword: &str, value: &str) -> Option<String> {
+    match keyword {
+        "mode" => u32::from_str_radix(value, 8).ok().map(|mode| format!("{:04o}", mode & 0o7777)),
+        "uid" | "gid" => value.parse::<u32>().ok().map(|id| id.to_string()),
+        "time" => {
+            let (secs, fraction) = value.split_once('.').unwrap_or((value, "0"));
+            let secs: u64 = secs.parse().ok()?;
+            let nanos: u32 = format!("{fraction:0<9}").get(..9)?.parse().ok()?;
+            Some(format!("{secs}.{nanos:09}"))
+        }
+        _ => None,
+    }
+}
+
+/// Write an mtree specification of `root` in the full-path format, one line
+/// per directory, regular file and symbolic link (which is not followed).
+/// With `digest`, files get a `sha256digest`.
+pub fn write_spec(root: &Path, out: &mut dyn Write, digest: bool) -> io::Result<()> {
+    writeln!(out, "#mtree v2.0")?;
+    let mut stack = vec![PathBuf::new()];
+    while let Some(rel) = stack.pop() {
+        let path = root.join(&rel);
+        let meta = fs::metadata(&path)?;
+        writeln!(out, "{} type=dir{}", spec_name(&rel), keywords(&meta))?;
+
+        let mut children: Vec<_> = fs::read_dir(&path)?.flatten().map(|e| e.path()).collect();
+        children.sort();
+        let mut subdirs = Vec::new();
+        for child in children {
+            let child_rel = rel.join(child.file_name().unwrap_or_default());
+            let meta = fs::symlink_metadata(&child)?;
+            if meta.is_dir() {
+                subdirs.push(child_rel);
+            } else if meta.is_symlink() {
+                let target = fs::read_link(&child)?;
+                writeln!(out, "{} type=link link={}{}", spec_name(&child_rel), encode(&target.to_string_lossy()), keywords(&meta))?;
+            } else if meta.is_file() {
+                write!(out, "{} type=file size={}{}", spec_name(&child_rel), meta.len(), keywords(&meta))?;
+                if digest {
+                    write!(out, " sha256digest={}", to_hex(&hash_file(&child, HashAlgo::Sha256)?))?;
+                }
+                writeln!(out)?;
+            }
+        }
+        // Reverse so the stack yields subdirectories in sorted order.
+        stack.extend(subdirs.into_iter().rev());
+    }
+    Ok(())
+}
+
+/// Parse an mtree specification, in either the full-path or the classic
+/// hierarchical format, returning the regular files and symbolic links it
+/// describes.
+pub fn parse_spec(text: &str) -> io::Result<BTreeMap<PathBuf, Expected>> {
+    let mut files = BTreeMap::new();
+    let mut defaults: HashMap<String, String> = HashMap::new();
+    let mut cwd = PathBuf::new();
+
+    // Join backslash-continued lines first.
+    let joined = text.replace("\\\n", " ");
+    for (lineno, line) in joined.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut tokens = line.split_whitespace();
+        let name = tokens.next().unwrap_or_default();
+        let mut kw = HashMap::new();
+        for token in tokens {
+            match token.split_once('=') {
+                Some((k, v)) => kw.insert(k.to_string(), v.to_string()),
+                None => kw.insert(token.to_string(), String::new()),
+            };
+        }
+
+        match name {
+            "/set" => {
+                defaults.extend(kw);
+                continue;
+            }
+            "/unset" => {
+                for k in kw.keys() {
+                    if k == "all" {
+                        defaults.clear();
+                    } else {
+                        defaults.remove(k);
+                    }
+                }
+                continue;
+            }
+            ".." => {
+                cwd.pop();
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut merged = defaults.clone();
+        merged.extend(kw);
+
+        let decoded = decode(name);
+        let full_path = decoded.contains('/');
+        let rel: PathBuf = if full_path {
+            Path::new(&decoded).components().filter(|c| c.as_os_str() != ".").collect()
+        } else if decoded == "." {
+            PathBuf::new()
+        } else {
+            cwd.join(&decoded)
+        };
+
+        let kind = merged.get("type").map_or("file", String::as_str);
+        match kind {
+            "dir" if !full_path => cwd = rel,
+            "dir" => {}
+            "file" | "link" => {
+                let size = match merged.get("size") {
+                    Some(s) => Some(s.parse().map_err(|_| {
+                        io::Error::new(io::ErrorKind::InvalidData, format!("line {}: invalid size {s:?}", lineno + 1))
+                    })?),
+                    None => None,
+                };
+                let digest = merged.get("sha256digest").or_else(|| merged.get("sha256")).map(|s| s.to_ascii_lowercase());
+                let mut metadata = BTreeMap::new();
+                for keyword in ["mode", "uid", "gid", "time"] {
+                    if let Some(value) = merged.get(keyword) {
+                        let normalized = normalize(keyword, value).ok_or_else(|| {
+                            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: invalid {keyword} {value:?}", lineno + 1))
+                        })?;
+                        metadata.insert(keyword.to_string(), normalized);
+                    }
+                }
+                let link = match (kind, merged.get("link")) {
+                    ("file", _) => None,
+                    (_, Some(target)) => Some(decode(target)),
+                    (_, None) => {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("line {}: link without a target", lineno + 1)));
+                    }
+                };
+                files.insert(rel, Expected { size, digest, metadata, link });
+            }
+            // Devices, sockets and FIFOs are not compared.
+            _ => {}
+        }
+    }
+    Ok(files)
+}
+
+/// Add a [`Category::Metadata`] entry to `result` for each file below `root`
+/// whose mode, owner or modification time differs from `spec`; files already
+/// reported as missing, changed or errored are left alone.
+pub fn check_metadata(root: &Path, spec: &BTreeMap<PathBuf, Expected>, result: &mut SubdirReport) {
+    for (rel, want) in spec {
+        if want.metadata.is_empty() || result.entries.iter().any(|e| &e.path == rel) {
+            continue;
+        }
+        let Ok(meta) = fs::symlink_metadata(root.join(rel)) else {
+            continue;
+        };
+        let attrs: Vec<_> = metadata_keywords(&meta)
+            .into_iter()
+            .filter_map(|(keyword, actual)| {
+                let expected = want.metadata.get(keyword)?;
+                (expected != &actual).then(|| AttrDiff::new(keyword, Some(expected.clone()), Some(actual)))
+            })
+            .collect();
+        if !attrs.is_empty() {
+            result.entries.push(DiffEntry { attrs, ..DiffEntry::new(Category::Metadata, rel.clone()) });
+        }
+    }
+    result.entries.sort_by_key(|e| e.category);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty scratch directory for the test called `name`.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dirdiff-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("d")).unwrap();
+        dir
+    }
+
+    /// Write a specification of `dir` and check `dir` against it.
+    fn round_trip(dir: &Path) -> (String, SubdirReport) {
+        let mut spec = Vec::new();
+        write_spec(dir, &mut spec, true).unwrap();
+        let text = String::from_utf8(spec).unwrap();
+        let spec = parse_spec(&text).unwrap();
+        let mut result = crate::compare::verify_files(dir, &spec, &[HashAlgo::Sha256], None);
+        check_metadata(dir, &spec, &mut result);
+        (text, result)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_are_listed_and_metadata_is_verified() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = scratch("mtree-metadata");
+        fs::write(dir.join("d/f"), "x").unwrap();
+        fs::set_permissions(dir.join("d/f"), fs::Permissions::from_mode(0o644)).unwrap();
+        std::os::unix::fs::symlink("..", dir.join("d/loop")).unwrap();
+
+        let mut spec = Vec::new();
+        write_spec(&dir, &mut spec, false).unwrap();
+        let spec = String::from_utf8(spec).unwrap();
+        assert_eq!(spec.lines().count(), 5);
+        assert!(spec.contains("./d/loop type=link link=.. "));
+
+        fs::set_permissions(dir.join("d/f"), fs::Permissions::from_mode(0o600)).unwrap();
+        let spec = parse_spec(&spec).unwrap();
+        let mut result = crate::compare::verify_files(&dir, &spec, &[HashAlgo::Sha256], None);
+        check_metadata(&dir, &spec, &mut result);
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].category, Category::Metadata);
+        assert_eq!(result.entries[0].attrs, [AttrDiff::new("mode", Some("0644".into()), Some("0600".into()))]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_to_files_round_trip() {
+        let dir = scratch("mtree-link");
+        fs::write(dir.join("d/f"), "x").unwrap();
+        std::os::unix::fs::symlink("f", dir.join("d/l")).unwrap();
+
+        let (spec, result) = round_trip(&dir);
+        assert!(spec.contains("./d/l type=link link=f "));
+        assert!(result.entries.is_empty(), "{:?}", result.entries);
+
+        fs::remove_file(dir.join("d/l")).unwrap();
+        std::os::unix::fs::symlink("g", dir.join("d/l")).unwrap();
+        let spec = parse_spec(&spec).unwrap();
+        let result = crate::compare::verify_files(&dir, &spec, &[HashAlgo::Sha256], None);
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].category, Category::Changed);
+        assert_eq!(result.entries[0].attrs, [AttrDiff::new("target", Some("f".into()), Some("g".into()))]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+//! Destinations for rendered output.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+
+/// Compression applied to the rendered output with `--compress`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+enum Sink {
+    Plain(Box<dyn Write>),
+    Gzip(GzEncoder<Box<dyn Write>>),
+    Zstd(zstd::Encoder<'static, Box<dyn Write>>),
+}
+
+impl Sink {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Sink::Plain(w) => w,
+            Sink::Gzip(w) => w,
+            Sink::Zstd(w) => w,
+        }
+    }
+
+    /// Write any compression trailer and flush.
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Sink::Plain(mut w) => w.flush(),
+            Sink::Gzip(w) => w.finish()?.flush(),
+            Sink::Zstd(w) => w.finish()?.flush(),
+        }
+    }
+}
+
+/// Either stdout or a file that is written atomically: output goes to a
+/// temporary file next to the target, which replaces the target only on
+/// [`Output::commit`]. Dropping an uncommitted output removes the temporary.
+pub struct Output {
+    sink: Option<Sink>,
+    file: Option<(PathBuf, PathBuf)>,
+}
+
+impl Output {
+    /// `--output` if given, stdout otherwise, optionally compressed on the fly.
+    pub fn open(path: Option<&Path>, compression: Option<Compression>) -> io::Result<Self> {
+        let (writer, file): (Box<dyn Write>, _) = match path {
+            None => (Box::new(io::stdout().lock()), None),
+            Some(path) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let tmp = path.with_file_name(format!(".{name}.tmp-{}", std::process::id()));
+                (Box::new(BufWriter::new(File::create(&tmp)?)), Some((tmp, path.to_path_buf())))
+            }
+        };
+        let sink = match compression {
+            None => Sink::Plain(writer),
+            Some(Compression::Gzip) => Sink::Gzip(GzEncoder::new(writer, flate2::Compression::default())),
+            Some(Compression::Zstd) => Sink::Zstd(zstd::Encoder::new(writer, 0)?),
+        };
+        Ok(Output { sink: Some(sink), file })
+    }
+
+    /// The file being written, if not stdout.
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|(_, target)| target.as_path())
+    }
+
+    /// Finish compression, flush and, for files, move the output into place.
+    pub fn commit(mut self) -> io::Result<()> {
+        if let Some(sink) = self.sink.take() {
+            sink.finish()?;
+        }
+        if let Some((tmp, target)) = self.file.take() {
+            fs::rename(tmp, target)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sink.as_mut().expect("output already committed").writer().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.as_mut().expect("output already committed").writer().flush()
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        // Close the file before removing it.
+        self.sink.take();
+        if let Some((tmp, _)) = self.file.take() {
+            let _ = fs::remove_file(tmp);
+        }
+    }
+}
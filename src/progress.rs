@@ -0,0 +1,44 @@
+//! Progress bar for `--hash` runs on big trees.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// Files and bytes compared so far, drawn to stderr against totals from a
+/// pre-scan of the files present on both sides.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    bar: ProgressBar,
+    files_done: Arc<AtomicU64>,
+    files_total: u64,
+}
+
+impl Progress {
+    pub fn new(files_total: u64, bytes_total: u64) -> Progress {
+        let bar = ProgressBar::with_draw_target(Some(bytes_total), ProgressDrawTarget::stderr());
+        bar.set_style(
+            ProgressStyle::with_template("{wide_bar} {binary_bytes}/{binary_total_bytes} {msg} ETA {eta}")
+                .expect("valid progress template"),
+        );
+        let progress = Progress { bar, files_done: Arc::new(AtomicU64::new(0)), files_total };
+        progress.bar.set_message(progress.files_message(0));
+        progress
+    }
+
+    fn files_message(&self, done: u64) -> String {
+        format!("{done}/{} files", self.files_total)
+    }
+
+    /// Record one more file pair compared, `bytes` being both sides' sizes.
+    pub fn advance(&self, bytes: u64) {
+        let done = self.files_done.fetch_add(1, Ordering::Relaxed) + 1;
+        self.bar.set_message(self.files_message(done));
+        self.bar.inc(bytes);
+    }
+
+    /// Remove the bar from the terminal.
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
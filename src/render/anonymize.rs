@@ -0,0 +1,119 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::Renderer;
+use crate::compare::to_hex;
+use crate::report::{AttrDiff, DiffEntry, Report, SubdirReport};
+
+/// Replaces every path component with a salted hash before handing the
+/// report to the wrapped renderer, so reports can be shared without leaking
+/// file names. The same component always maps to the same token within a
+/// run, and across runs that use the same salt.
+///
+/// Link targets are anonymized like paths, and other attribute values that
+/// may name things (owners, extended attributes) become tokens too. Error
+/// messages are replaced, as they can quote paths.
+///
+/// Formats that consult the filesystem (brief, itemize) only see the
+/// anonymized paths and fall back to what the entries themselves carry.
+pub struct Anonymize {
+    inner: Box<dyn Renderer>,
+    salt: String,
+    /// The anonymized counterpart of the report built so far.
+    report: Option<Report>,
+}
+
+impl Anonymize {
+    /// Without a salt a random one is used, so tokens are only stable within
+    /// this run.
+    pub fn new(inner: Box<dyn Renderer>, salt: Option<String>) -> Self {
+        let salt = salt.unwrap_or_else(|| format!("{:016x}", RandomState::new().build_hasher().finish()));
+        Anonymize { inner, salt, report: None }
+    }
+
+    fn component(&self, name: &std::ffi::OsStr) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update([0]);
+        hasher.update(name.as_encoded_bytes());
+        to_hex(&hasher.finalize()[..6])
+    }
+
+    fn path(&self, path: &Path) -> PathBuf {
+        path.components()
+            .map(|c| match c {
+                Component::Normal(name) => PathBuf::from(self.component(name)),
+                other => PathBuf::from(other.as_os_str()),
+            })
+            .collect()
+    }
+
+    fn attr_of(&self, attr: &AttrDiff) -> AttrDiff {
+        let value = |v: &Option<String>| match attr.name.as_str() {
+            "type" | "mode" | "mtime" | "btime" | "flags" | "links" | "allocated" | "attributes" | "device" => v.clone(),
+            "target" => v.as_deref().map(|t| self.path(Path::new(t)).display().to_string()),
+            _ => v.as_deref().map(|v| self.component(v.as_ref())),
+        };
+        AttrDiff::new(attr.name.clone(), value(&attr.a), value(&attr.b))
+    }
+
+    fn entry_of(&self, entry: &DiffEntry) -> DiffEntry {
+        DiffEntry {
+            path: self.path(&entry.path),
+            attrs: entry.attrs.iter().map(|a| self.attr_of(a)).collect(),
+            error: entry.error.as_ref().map(|_| "error (message withheld by --anonymize)".to_string()),
+            ..entry.clone()
+        }
+    }
+
+    fn subdir_of(&self, sub: &SubdirReport) -> SubdirReport {
+        SubdirReport {
+            name: self.path(&sub.name),
+            status: sub.status,
+            entries: sub.entries.iter().map(|e| self.entry_of(e)).collect(),
+            ..sub.clone()
+        }
+    }
+
+    fn report(&mut self, report: &Report) -> &mut Report {
+        if self.report.is_none() {
+            self.report = Some(Report {
+                content: report.content,
+                algo: report.algo,
+                mtime: report.mtime,
+                ..Report::new(self.path(&report.dir_a), self.path(&report.dir_b), report.hash)
+            });
+        }
+        let anonymized = self.report.as_mut().expect("just initialized");
+        anonymized.elapsed_secs = report.elapsed_secs;
+        anonymized
+    }
+}
+
+impl Renderer for Anonymize {
+    fn entry(&mut self, out: &mut dyn Write, report: &Report, sub: &Path, entry: &DiffEntry) -> io::Result<()> {
+        let (sub, entry) = (self.path(sub), self.entry_of(entry));
+        self.report(report);
+        let report = self.report.as_ref().expect("initialized above");
+        self.inner.entry(out, report, &sub, &entry)
+    }
+
+    fn subdir(&mut self, out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        let sub = self.subdir_of(sub);
+        self.report(report);
+        let report = self.report.as_mut().expect("initialized above");
+        self.inner.subdir(out, report, &sub)?;
+        report.subdirs.push(sub);
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        self.report(report);
+        let report = self.report.as_ref().expect("initialized above");
+        self.inner.finish(out, report)
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::Renderer;
+use crate::report::{Category, DiffEntry, Report, SubdirReport, SubdirStatus};
+
+/// Streams lines in the format of GNU `diff -rq`.
+#[derive(Default)]
+pub struct BriefRenderer {
+    /// `Only in` lines already printed; `diff -rq` reports a missing
+    /// directory once instead of every file below it.
+    reported: HashSet<PathBuf>,
+}
+
+/// The topmost component of `rel` that does not exist below `other`, joined
+/// onto `present`, i.e. what `diff -rq` names in its `Only in` line.
+fn only_in(present: &Path, other: &Path, rel: &Path) -> PathBuf {
+    let mut prefix = PathBuf::new();
+    for component in rel.components() {
+        prefix.push(component);
+        if !other.join(&prefix).exists() {
+            break;
+        }
+    }
+    present.join(prefix)
+}
+
+/// `rel` below `dir`; `dir` itself for the `.` entry of a subdirectory that
+/// is compared as a whole, e.g. as a symlink or against a file.
+fn entry_path(dir: &Path, rel: &Path) -> PathBuf {
+    if rel == Path::new(".") { dir.to_path_buf() } else { dir.join(rel) }
+}
+
+/// How `diff` names a file of `kind` and `size`.
+fn gnu_kind(kind: &str, size: Option<u64>) -> &str {
+    match kind {
+        "file" if size == Some(0) => "regular empty file",
+        "file" => "regular file",
+        "dir" => "directory",
+        "symlink" => "symbolic link",
+        "fifo" => "fifo",
+        "socket" => "socket",
+        "char-device" => "character special file",
+        "block-device" => "block special file",
+        other => other,
+    }
+}
+
+impl BriefRenderer {
+    fn write_only_in(&mut self, out: &mut dyn Write, path: PathBuf) -> io::Result<()> {
+        if !self.reported.insert(path.clone()) {
+            return Ok(());
+        }
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let name = path.file_name().unwrap_or_default();
+        writeln!(out, "Only in {}: {}", dir.display(), name.to_string_lossy())
+    }
+}
+
+impl Renderer for BriefRenderer {
+    fn entry(&mut self, out: &mut dyn Write, report: &Report, sub: &Path, entry: &DiffEntry) -> io::Result<()> {
+        let a = report.dir_a.join(sub);
+        let b = report.dir_b.join(sub);
+        match entry.category {
+            Category::MissingInB => self.write_only_in(out, only_in(&a, &b, &entry.path)),
+            Category::MissingInA => self.write_only_in(out, only_in(&b, &a, &entry.path)),
+            Category::TypeConflict => {
+                let (kind_a, kind_b) = entry.kinds();
+                let (kind_a, kind_b) = (gnu_kind(kind_a, entry.size_a), gnu_kind(kind_b, entry.size_b));
+                writeln!(
+                    out,
+                    "File {} is a {kind_a} while file {} is a {kind_b}",
+                    entry_path(&a, &entry.path).display(),
+                    entry_path(&b, &entry.path).display()
+                )
+            }
+            Category::Changed => writeln!(
+                out,
+                "Files {} and {} differ",
+                entry_path(&a, &entry.path).display(),
+                entry_path(&b, &entry.path).display()
+            ),
+            Category::Metadata => writeln!(
+                out,
+                "Metadata of {} and {} differs: {}",
+                entry_path(&a, &entry.path).display(),
+                entry_path(&b, &entry.path).display(),
+                entry.attr_names()
+            ),
+            Category::DanglingInA | Category::DanglingInB => {
+                let root = if entry.category == Category::DanglingInA { &a } else { &b };
+                writeln!(out, "Dangling symlink {} -> {}", entry_path(root, &entry.path).display(), entry.target().unwrap_or("?"))
+            }
+            Category::Same => writeln!(
+                out,
+                "Files {} and {} are identical",
+                entry_path(&a, &entry.path).display(),
+                entry_path(&b, &entry.path).display()
+            ),
+            Category::Errored => {
+                // Like diff, errors go to stderr and do not pollute the listing.
+                eprintln!("dir_compare: {}: {}", entry_path(&a, &entry.path).display(), entry.error.as_deref().unwrap_or(""));
+                Ok(())
+            }
+        }
+    }
+
+    fn subdir(&mut self, out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        match sub.status {
+            SubdirStatus::OnlyInA => self.write_only_in(out, report.dir_a.join(&sub.name)),
+            SubdirStatus::OnlyInB => self.write_only_in(out, report.dir_b.join(&sub.name)),
+            SubdirStatus::Compared => Ok(()),
+        }
+    }
+}
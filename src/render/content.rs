@@ -0,0 +1,184 @@
+//! Line diffs of the contents of changed text files, shown below their
+//! entries in the text report.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use similar::udiff::UnifiedHunkHeader;
+use similar::{ChangeTag, InlineChange, TextDiff};
+
+use super::Palette;
+
+/// Indentation of the diff lines below an entry.
+const INDENT: &str = "      ";
+
+/// The contents of the file at `path` if it is text: valid UTF-8 without
+/// NUL bytes.
+fn read_text(path: &Path) -> Option<String> {
+    let text = String::from_utf8(fs::read(path).ok()?).ok()?;
+    (!text.contains('\0')).then_some(text)
+}
+
+/// How changed text files are diffed below their entries.
+#[derive(Clone, Copy, Debug)]
+pub struct ContentDiff {
+    /// Unchanged lines shown around each change.
+    pub context: usize,
+    pub layout: Layout,
+    /// Highlight the changed words within changed lines.
+    pub words: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// `-`/`+` lines one below the other, like `diff -u`.
+    Unified,
+    /// The two files in columns next to each other, fitting this many
+    /// terminal columns, like `diff -y`.
+    SideBySide(usize),
+}
+
+impl ContentDiff {
+    /// Write the diff of the text files `a` and `b`; files that are not both
+    /// text are left out.
+    pub fn write(&self, out: &mut dyn Write, a: &Path, b: &Path, palette: Palette) -> io::Result<()> {
+        let (Some(x), Some(y)) = (read_text(a), read_text(b)) else {
+            return Ok(());
+        };
+        match self.layout {
+            Layout::Unified => self.write_unified(out, (a, &x), (b, &y), palette),
+            Layout::SideBySide(width) => write_side_by_side(out, &x, &y, self.context, width, palette),
+        }
+    }
+
+    /// A unified diff of the files `a` and `b` with contents `x` and `y`.
+    fn write_unified(&self, out: &mut dyn Write, (a, x): (&Path, &str), (b, y): (&Path, &str), palette: Palette) -> io::Result<()> {
+        let Palette { red, green, cyan, reset, .. } = palette;
+        let diff = TextDiff::from_lines(x, y);
+        let groups = diff.grouped_ops(self.context);
+        if groups.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, "{INDENT}{red}--- {}{reset}", a.display())?;
+        writeln!(out, "{INDENT}{green}+++ {}{reset}", b.display())?;
+        for group in groups {
+            writeln!(out, "{INDENT}{cyan}{}{reset}", UnifiedHunkHeader::new(&group))?;
+            for op in &group {
+                if self.words {
+                    for change in diff.iter_inline_changes(op) {
+                        write_inline(out, &change, palette)?;
+                    }
+                    continue;
+                }
+                for change in diff.iter_changes(op) {
+                    let line = change.value().trim_end_matches(['\n', '\r']);
+                    match change.tag() {
+                        ChangeTag::Delete => writeln!(out, "{INDENT}{red}-{line}{reset}")?,
+                        ChangeTag::Insert => writeln!(out, "{INDENT}{green}+{line}{reset}")?,
+                        ChangeTag::Equal => writeln!(out, "{INDENT} {line}")?,
+                    }
+                    if change.missing_newline() {
+                        writeln!(out, "{INDENT}\\ No newline at end of file")?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Width of the terminal on stdout, from `COLUMNS` or the terminal itself;
+/// 130 columns (as `diff -y`) when neither tells.
+pub fn terminal_width() -> usize {
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+        return columns;
+    }
+    #[cfg(unix)]
+    {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        // SAFETY: TIOCGWINSZ only writes the window size into `size`.
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+            return usize::from(size.ws_col);
+        }
+    }
+    130
+}
+
+/// One line of a unified diff with its emphasized words highlighted: in
+/// reverse video when colored, otherwise marked as `[-deleted-]` and
+/// `{+inserted+}` like `git diff --word-diff`.
+fn write_inline(out: &mut dyn Write, change: &InlineChange<str>, palette: Palette) -> io::Result<()> {
+    let Palette { red, green, reverse, reset, .. } = palette;
+    let (sign, color, open, close) = match change.tag() {
+        ChangeTag::Delete => ('-', red, "[-", "-]"),
+        ChangeTag::Insert => ('+', green, "{+", "+}"),
+        ChangeTag::Equal => (' ', "", "", ""),
+    };
+    let mut line = String::new();
+    for (emphasized, value) in change.iter_strings_lossy() {
+        let value = value.trim_end_matches(['\n', '\r']);
+        match (emphasized, reset.is_empty()) {
+            (false, _) => line.push_str(value),
+            (true, true) => line.push_str(&format!("{open}{value}{close}")),
+            (true, false) => line.push_str(&format!("{reverse}{value}{reset}{color}")),
+        }
+    }
+    let reset = if color.is_empty() { "" } else { reset };
+    writeln!(out, "{INDENT}{color}{sign}{line}{reset}")?;
+    if change.missing_newline() {
+        writeln!(out, "{INDENT}\\ No newline at end of file")?;
+    }
+    Ok(())
+}
+
+/// `x` and `y` in two columns within `width`, with a gutter marking the
+/// changed (`|`), deleted (`<`) and inserted (`>`) lines.
+fn write_side_by_side(out: &mut dyn Write, x: &str, y: &str, context: usize, width: usize, palette: Palette) -> io::Result<()> {
+    let Palette { red, green, cyan, reset, .. } = palette;
+    let diff = TextDiff::from_lines(x, y);
+    let (old, new): (Vec<&str>, Vec<&str>) = (x.split_inclusive('\n').collect(), y.split_inclusive('\n').collect());
+    let column = (width.saturating_sub(INDENT.len() + 3) / 2).max(10);
+    for (i, group) in diff.grouped_ops(context).into_iter().enumerate() {
+        if i > 0 {
+            writeln!(out, "{INDENT}{cyan}{:-^width$}{reset}", "", width = 2 * column + 3)?;
+        }
+        for op in group {
+            let (_, old_range, new_range) = op.as_tag_tuple();
+            let rows = old_range.len().max(new_range.len());
+            for row in 0..rows {
+                let left = old.get(old_range.start + row).filter(|_| row < old_range.len());
+                let right = new.get(new_range.start + row).filter(|_| row < new_range.len());
+                let (gutter, color) = match (left, right) {
+                    (Some(l), Some(r)) if l == r => (' ', ""),
+                    (Some(_), Some(_)) => ('|', cyan),
+                    (Some(_), None) => ('<', red),
+                    _ => ('>', green),
+                };
+                let left = fit(left.copied().unwrap_or(""), column);
+                let right = fit(right.copied().unwrap_or(""), column);
+                writeln!(out, "{INDENT}{color}{left} {gutter} {}{reset}", right.trim_end())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `line` without its line ending and with tabs expanded, cut or padded to
+/// exactly `width` characters.
+fn fit(line: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut len = 0;
+    for c in line.trim_end_matches(['\n', '\r']).chars() {
+        let (c, n) = if c == '\t' { (' ', 8 - len % 8) } else { (c, 1) };
+        for _ in 0..n {
+            if len == width {
+                return fitted;
+            }
+            fitted.push(c);
+            len += 1;
+        }
+    }
+    fitted.extend(std::iter::repeat_n(' ', width - len));
+    fitted
+}
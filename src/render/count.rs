@@ -0,0 +1,24 @@
+use std::io::{self, Write};
+
+use super::Renderer;
+use crate::report::{Category, Report};
+
+/// Prints only the totals, as a single line of `key=value` pairs.
+pub struct CountRenderer;
+
+impl Renderer for CountRenderer {
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        writeln!(
+            out,
+            "missing_a={} missing_b={} type_conflict={} changed={} metadata={} dangling_a={} dangling_b={} errored={}",
+            report.count(Category::MissingInA),
+            report.count(Category::MissingInB),
+            report.count(Category::TypeConflict),
+            report.count(Category::Changed),
+            report.count(Category::Metadata),
+            report.count(Category::DanglingInA),
+            report.count(Category::DanglingInB),
+            report.count(Category::Errored)
+        )
+    }
+}
@@ -0,0 +1,59 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::Renderer;
+use crate::report::{DiffEntry, Report, SubdirReport, SubdirStatus};
+
+const HEADER: [&str; 6] = ["category", "subdir", "relative_path", "size_a", "size_b", "error"];
+
+/// Streams one CSV row per difference, preceded by a header row.
+#[derive(Default)]
+pub struct CsvRenderer {
+    header_written: bool,
+}
+
+impl CsvRenderer {
+    fn write_row(&mut self, out: &mut dyn Write, row: [&str; 6]) -> io::Result<()> {
+        let mut writer = ::csv::WriterBuilder::new().has_headers(false).from_writer(out);
+        if !self.header_written {
+            writer.write_record(HEADER)?;
+            self.header_written = true;
+        }
+        writer.write_record(row)?;
+        writer.flush()
+    }
+}
+
+fn size(size: Option<u64>) -> String {
+    size.map(|s| s.to_string()).unwrap_or_default()
+}
+
+impl Renderer for CsvRenderer {
+    fn entry(&mut self, out: &mut dyn Write, _report: &Report, sub: &Path, entry: &DiffEntry) -> io::Result<()> {
+        self.write_row(out, [
+            entry.category.as_str(),
+            &sub.to_string_lossy(),
+            &entry.path.to_string_lossy(),
+            &size(entry.size_a),
+            &size(entry.size_b),
+            entry.error.as_deref().unwrap_or(""),
+        ])
+    }
+
+    fn subdir(&mut self, out: &mut dyn Write, _report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        // Subdirectories missing on one side are reported as a single row.
+        if sub.status == SubdirStatus::Compared {
+            return Ok(());
+        }
+        self.write_row(out, [sub.status.as_str(), &sub.name.to_string_lossy(), "", "", "", ""])
+    }
+
+    fn finish(&mut self, out: &mut dyn Write, _report: &Report) -> io::Result<()> {
+        if !self.header_written {
+            let mut writer = ::csv::WriterBuilder::new().has_headers(false).from_writer(out);
+            writer.write_record(HEADER)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
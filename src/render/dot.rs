@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::Renderer;
+use crate::report::{Category, Report, SubdirStatus};
+
+/// Writes a Graphviz digraph of the directories that contain differences,
+/// filled by the kind of difference found below each of them.
+pub struct DotRenderer;
+
+/// Files only in A (`+`), only in B (`-`) and changed below a directory, as
+/// in the text report's rollups.
+#[derive(Default, Clone, Copy)]
+struct Tally {
+    only_a: usize,
+    only_b: usize,
+    changed: usize,
+}
+
+impl Tally {
+    fn color(self) -> &'static str {
+        match (self.only_a > 0, self.only_b > 0, self.changed > 0) {
+            (true, false, false) => "palegreen",
+            (false, true, false) => "lightcoral",
+            (false, false, true) => "orange",
+            (false, false, false) => "white",
+            _ => "khaki",
+        }
+    }
+}
+
+/// Quote a string as a DOT ID; newlines become `\n` line breaks in labels.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+/// Add `tally` to `dir` and each of its ancestors, up to the root.
+fn add(tallies: &mut BTreeMap<PathBuf, Tally>, dir: &Path, tally: Tally) {
+    for ancestor in dir.ancestors() {
+        let t = tallies.entry(ancestor.to_path_buf()).or_default();
+        t.only_a += tally.only_a;
+        t.only_b += tally.only_b;
+        t.changed += tally.changed;
+    }
+}
+
+impl Renderer for DotRenderer {
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        let mut tallies: BTreeMap<PathBuf, Tally> = BTreeMap::new();
+        tallies.insert(PathBuf::new(), Tally::default());
+        for sub in &report.subdirs {
+            match sub.status {
+                SubdirStatus::OnlyInA => add(&mut tallies, &sub.name, Tally { only_a: sub.stats.files_a, ..Tally::default() }),
+                SubdirStatus::OnlyInB => add(&mut tallies, &sub.name, Tally { only_b: sub.stats.files_b, ..Tally::default() }),
+                SubdirStatus::Compared => {
+                    for entry in &sub.entries {
+                        let n = entry.files.unwrap_or(1);
+                        let tally = match entry.category {
+                            Category::MissingInA => Tally { only_b: n, ..Tally::default() },
+                            Category::MissingInB => Tally { only_a: n, ..Tally::default() },
+                            Category::TypeConflict | Category::Changed | Category::Metadata | Category::DanglingInA | Category::DanglingInB | Category::Errored => Tally { changed: n, ..Tally::default() },
+                            Category::Same => continue,
+                        };
+                        let full = sub.name.join(&entry.path);
+                        // A collapsed directory gets a node of its own.
+                        let dir = if entry.is_dir() { full.as_path() } else { full.parent().unwrap_or(Path::new("")) };
+                        add(&mut tallies, dir, tally);
+                    }
+                }
+            }
+        }
+
+        writeln!(out, "digraph dir_compare {{")?;
+        writeln!(out, "  rankdir=LR;")?;
+        writeln!(out, "  node [shape=box, style=filled, fontname=\"monospace\"];")?;
+        for (dir, tally) in &tallies {
+            let name = if dir.as_os_str().is_empty() {
+                format!("{} vs {}", report.dir_a.display(), report.dir_b.display())
+            } else {
+                dir.file_name().unwrap_or_default().to_string_lossy().into_owned()
+            };
+            let label = format!("{name}\n+{} -{} ~{}", tally.only_a, tally.only_b, tally.changed);
+            writeln!(out, "  {} [label={}, fillcolor={}];", quote(&dir.to_string_lossy()), quote(&label), tally.color())?;
+            if let Some(parent) = dir.parent() {
+                writeln!(out, "  {} -> {};", quote(&parent.to_string_lossy()), quote(&dir.to_string_lossy()))?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+}
@@ -0,0 +1,72 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::Renderer;
+use crate::report::{Category, DiffEntry, Report, SubdirReport, SubdirStatus};
+
+/// Streams GitHub Actions workflow commands so differences show up as
+/// annotations in the Actions UI.
+pub struct GhaRenderer;
+
+/// Escape the message part of a workflow command.
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escape a `key=value` property of a workflow command.
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+impl Renderer for GhaRenderer {
+    fn entry(&mut self, out: &mut dyn Write, report: &Report, sub: &Path, entry: &DiffEntry) -> io::Result<()> {
+        let rel = sub.join(&entry.path);
+        let (level, file, message) = match entry.category {
+            Category::MissingInB => ("error", report.dir_a.join(&rel), format!("missing in {}", report.dir_b.display())),
+            Category::MissingInA => ("warning", report.dir_b.join(&rel), format!("missing in {}", report.dir_a.display())),
+            Category::TypeConflict => {
+                let (kind_a, kind_b) = entry.kinds();
+                ("error", report.dir_a.join(&rel), format!("is a {kind_a} but a {kind_b} in {}", report.dir_b.display()))
+            }
+            Category::Changed => ("error", report.dir_a.join(&rel), format!("content differs from {}", report.dir_b.join(&rel).display())),
+            Category::Metadata => (
+                "warning",
+                report.dir_a.join(&rel),
+                format!("{} differs from {}", entry.attr_names(), report.dir_b.join(&rel).display()),
+            ),
+            Category::DanglingInA | Category::DanglingInB => (
+                "warning",
+                if entry.category == Category::DanglingInA { report.dir_a.join(&rel) } else { report.dir_b.join(&rel) },
+                format!("dangling symlink to {}", entry.target().unwrap_or("?")),
+            ),
+            Category::Errored => (
+                "error",
+                report.dir_a.join(&rel),
+                format!("could not be compared: {}", entry.error.as_deref().unwrap_or("")),
+            ),
+            Category::Same => return Ok(()),
+        };
+        writeln!(
+            out,
+            "::{level} file={},title={}::{}",
+            escape_property(&file.to_string_lossy()),
+            escape_property(entry.category.as_str()),
+            escape_data(&format!("{}: {message}", rel.display())),
+        )
+    }
+
+    fn subdir(&mut self, out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        let (present, missing) = match sub.status {
+            SubdirStatus::OnlyInA => (&report.dir_a, &report.dir_b),
+            SubdirStatus::OnlyInB => (&report.dir_b, &report.dir_a),
+            SubdirStatus::Compared => return Ok(()),
+        };
+        writeln!(
+            out,
+            "::error file={},title={}::{}",
+            escape_property(&present.join(&sub.name).to_string_lossy()),
+            escape_property(sub.status.as_str()),
+            escape_data(&format!("{}: MISSING entirely in {}", sub.name.display(), missing.display())),
+        )
+    }
+}
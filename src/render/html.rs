@@ -0,0 +1,111 @@
+use std::io::{self, Write};
+
+use super::{escape_markup as escape, Renderer};
+use crate::report::{Category, Report, SubdirReport, SubdirStatus};
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.4em; }
+table.summary { border-collapse: collapse; margin-bottom: 1.5em; }
+table.summary td, table.summary th { border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }
+details { border: 1px solid #ddd; border-radius: 4px; margin: 0.4em 0; padding: 0.3em 0.8em; }
+summary { cursor: pointer; font-weight: bold; }
+.counts { font-weight: normal; color: #666; margin-left: 0.5em; }
+.identical { color: #2a7d2a; }
+.missing { color: #b8860b; }
+.changed { color: #c0392b; }
+.conflict { color: #a93226; font-weight: bold; }
+.metadata { color: #d35400; }
+.dangling { color: #7f8c8d; }
+.errored { color: #8e44ad; }
+ul { margin: 0.3em 0; font-family: ui-monospace, monospace; }
+";
+
+/// Writes a single self-contained HTML document once the report is complete.
+pub struct HtmlRenderer;
+
+fn css_class(category: Category) -> &'static str {
+    match category {
+        Category::MissingInB | Category::MissingInA => "missing",
+        Category::TypeConflict => "conflict",
+        Category::Changed => "changed",
+        Category::Metadata => "metadata",
+        Category::DanglingInA | Category::DanglingInB => "dangling",
+        Category::Errored => "errored",
+        Category::Same => "identical",
+    }
+}
+
+fn write_subdir(out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+    let name = escape(&sub.name.to_string_lossy());
+    let a = escape(&report.dir_a.display().to_string());
+    let b = escape(&report.dir_b.display().to_string());
+
+    match sub.status {
+        SubdirStatus::OnlyInA => {
+            return writeln!(out, "<details><summary class=\"missing\">{name}</summary><p class=\"missing\">Present in {a} but MISSING entirely in {b}</p></details>");
+        }
+        SubdirStatus::OnlyInB => {
+            return writeln!(out, "<details><summary class=\"missing\">{name}</summary><p class=\"missing\">Present in {b} but MISSING entirely in {a}</p></details>");
+        }
+        SubdirStatus::Compared => {}
+    }
+
+    if sub.is_identical() {
+        let what = if report.hash { "identical files and contents" } else { "identical file sets (skipped content check)" };
+        return writeln!(out, "<details><summary class=\"identical\">{name}</summary><p class=\"identical\">{what}</p></details>");
+    }
+
+    let counts: Vec<String> = Category::ALL
+        .iter()
+        .map(|&c| (c, sub.count(c)))
+        .filter(|&(_, n)| n > 0)
+        .map(|(c, n)| format!("{} {n}", c.as_str()))
+        .collect();
+    writeln!(out, "<details open><summary>{name}<span class=\"counts\">{}</span></summary>", counts.join(", "))?;
+
+    let (path_a, path_b) = report.subdir_paths(sub);
+    let (path_a, path_b) = (escape(&path_a.display().to_string()), escape(&path_b.display().to_string()));
+    for category in Category::ALL {
+        let mut entries = sub.entries_in(category).peekable();
+        if entries.peek().is_none() {
+            continue;
+        }
+        let class = css_class(category);
+        writeln!(out, "<h3 class=\"{class}\">{}</h3>\n<ul class=\"{class}\">", category.label(&path_a, &path_b))?;
+        for entry in entries {
+            let path = escape(&entry.path.to_string_lossy());
+            match &entry.error {
+                Some(e) => writeln!(out, "<li>{path} — {}</li>", escape(e))?,
+                None => writeln!(out, "<li>{path}</li>")?,
+            }
+        }
+        writeln!(out, "</ul>")?;
+    }
+    writeln!(out, "</details>")
+}
+
+impl Renderer for HtmlRenderer {
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        let a = escape(&report.dir_a.display().to_string());
+        let b = escape(&report.dir_b.display().to_string());
+
+        writeln!(out, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+        writeln!(out, "<title>dir_compare: {a} vs {b}</title>\n<style>{STYLE}</style>\n</head>\n<body>")?;
+        writeln!(out, "<h1>{a} vs {b}</h1>")?;
+
+        writeln!(out, "<table class=\"summary\">")?;
+        writeln!(out, "<tr><th>Subdirectories</th><td>{}</td></tr>", report.subdirs.len())?;
+        for category in Category::ALL {
+            let class = css_class(category);
+            writeln!(out, "<tr><th class=\"{class}\">{}</th><td>{}</td></tr>", category.as_str(), report.count(category))?;
+        }
+        writeln!(out, "</table>")?;
+
+        for sub in &report.subdirs {
+            write_subdir(out, report, sub)?;
+        }
+
+        writeln!(out, "</body>\n</html>")
+    }
+}
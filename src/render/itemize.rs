@@ -0,0 +1,103 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::Renderer;
+use crate::report::{Category, DiffEntry, Report, SubdirReport, SubdirStatus};
+
+/// Streams rsync `--itemize-changes` lines describing what
+/// `rsync -rcni --delete DIRECTORY_A/ DIRECTORY_B/` would do.
+#[derive(Default)]
+pub struct ItemizeRenderer {
+    /// Directories already announced as created (`cd+++++++++`).
+    created: HashSet<PathBuf>,
+}
+
+impl ItemizeRenderer {
+    /// Announce every ancestor directory of `rel` that is missing in B.
+    fn create_parents(&mut self, out: &mut dyn Write, report: &Report, rel: &Path) -> io::Result<()> {
+        let mut ancestors: Vec<_> = rel.ancestors().skip(1).filter(|p| !p.as_os_str().is_empty()).collect();
+        ancestors.reverse();
+        for dir in ancestors {
+            if !report.dir_b.join(dir).is_dir() && self.created.insert(dir.to_path_buf()) {
+                writeln!(out, "cd+++++++++ {}/", dir.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Itemized string for a file whose contents differ.
+fn changed_item(report: &Report, rel: &Path, entry: &DiffEntry) -> String {
+    let size = if entry.size_a != entry.size_b { 's' } else { '.' };
+    let mtime_a = fs::metadata(report.dir_a.join(rel)).and_then(|m| m.modified()).ok();
+    let mtime_b = fs::metadata(report.dir_b.join(rel)).and_then(|m| m.modified()).ok();
+    let time = if mtime_a != mtime_b { 't' } else { '.' };
+    format!(">fc{size}{time}......")
+}
+
+/// Itemized string for a file or directory whose metadata alone differs,
+/// flagging the (directory) modification time (`t`), permissions (`p`), owner
+/// (`o`), group (`g`) and xattrs (`x`, including the ones reported under their
+/// own names).
+fn metadata_item(entry: &DiffEntry) -> String {
+    let has = |name: &str| entry.attrs.iter().any(|a| a.name == name || a.name.starts_with(&format!("{name}:")));
+    let flag = |name, c| if has(name) { c } else { '.' };
+    let kind = if entry.path.as_os_str().to_string_lossy().ends_with('/') { 'd' } else { 'f' };
+    let xattrs = ["xattr", "selinux", "caps", "resource-fork", "finder-info", "quarantine"];
+    let xattr = if xattrs.iter().any(|name| has(name)) { 'x' } else { '.' };
+    format!(".{kind}..{}{}{}{}..{xattr}", flag("mtime", 't'), flag("mode", 'p'), flag("owner", 'o'), flag("group", 'g'))
+}
+
+impl Renderer for ItemizeRenderer {
+    fn entry(&mut self, out: &mut dyn Write, report: &Report, sub: &Path, entry: &DiffEntry) -> io::Result<()> {
+        let rel = sub.join(&entry.path);
+        match entry.category {
+            Category::MissingInB if entry.is_dir() => {
+                self.create_parents(out, report, &rel)?;
+                self.created.insert(rel.clone());
+                writeln!(out, "cd+++++++++ {}/", rel.display())
+            }
+            Category::MissingInB => {
+                self.create_parents(out, report, &rel)?;
+                writeln!(out, ">f+++++++++ {}", rel.display())
+            }
+            Category::MissingInA if entry.is_dir() => writeln!(out, "*deleting   {}/", rel.display()),
+            Category::MissingInA => writeln!(out, "*deleting   {}", rel.display()),
+            Category::TypeConflict => {
+                // Like rsync: the B side is deleted and the A side created anew.
+                let (kind_a, kind_b) = entry.kinds();
+                let slash = if kind_b == "dir" { "/" } else { "" };
+                writeln!(out, "*deleting   {}{slash}", rel.display())?;
+                match kind_a {
+                    "dir" => writeln!(out, "cd+++++++++ {}/", rel.display()),
+                    "symlink" => writeln!(out, "cL+++++++++ {}", rel.display()),
+                    _ => writeln!(out, ">f+++++++++ {}", rel.display()),
+                }
+            }
+            Category::Changed => writeln!(out, "{} {}", changed_item(report, &rel, entry), rel.display()),
+            Category::Metadata => writeln!(out, "{} {}", metadata_item(entry), rel.display()),
+            Category::Same => Ok(()),
+            Category::DanglingInA | Category::DanglingInB => {
+                eprintln!("dir_compare: {}: dangling symlink to {}", rel.display(), entry.target().unwrap_or("?"));
+                Ok(())
+            }
+            Category::Errored => {
+                eprintln!("dir_compare: {}: {}", rel.display(), entry.error.as_deref().unwrap_or(""));
+                Ok(())
+            }
+        }
+    }
+
+    fn subdir(&mut self, out: &mut dyn Write, _report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        match sub.status {
+            SubdirStatus::OnlyInA => {
+                self.created.insert(sub.name.clone());
+                writeln!(out, "cd+++++++++ {}/", sub.name.display())
+            }
+            SubdirStatus::OnlyInB => writeln!(out, "*deleting   {}/", sub.name.display()),
+            SubdirStatus::Compared => Ok(()),
+        }
+    }
+}
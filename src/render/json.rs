@@ -0,0 +1,13 @@
+use std::io::{self, Write};
+
+use super::Renderer;
+use crate::report::Report;
+
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, report)?;
+        writeln!(out)
+    }
+}
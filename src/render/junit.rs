@@ -0,0 +1,68 @@
+use std::io::{self, Write};
+
+use super::{escape_markup as escape, Renderer};
+use crate::report::{Category, Report, SubdirReport, SubdirStatus};
+
+/// Writes JUnit XML where each subdirectory is a test case that fails when
+/// any difference was found in it.
+pub struct JunitRenderer;
+
+/// Short failure message and detailed body for a subdirectory, or `None`
+/// when it passed.
+fn failure(report: &Report, sub: &SubdirReport) -> Option<(String, String)> {
+    let a = report.dir_a.display();
+    let b = report.dir_b.display();
+    match sub.status {
+        SubdirStatus::OnlyInA => {
+            let msg = format!("Present in {a} but MISSING entirely in {b}");
+            return Some((msg.clone(), msg));
+        }
+        SubdirStatus::OnlyInB => {
+            let msg = format!("Present in {b} but MISSING entirely in {a}");
+            return Some((msg.clone(), msg));
+        }
+        SubdirStatus::Compared if sub.is_identical() => return None,
+        SubdirStatus::Compared => {}
+    }
+
+    let counts: Vec<String> = Category::ALL
+        .iter()
+        .map(|&c| (c, sub.count(c)))
+        .filter(|&(_, n)| n > 0)
+        .map(|(c, n)| format!("{n} {}", c.as_str()))
+        .collect();
+    let mut body = String::new();
+    for entry in &sub.entries {
+        body.push_str(&format!("{}: {}", entry.category.as_str(), entry.path.display()));
+        if let Some(e) = &entry.error {
+            body.push_str(&format!(" ({e})"));
+        }
+        body.push('\n');
+    }
+    Some((counts.join(", "), body))
+}
+
+impl Renderer for JunitRenderer {
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        let failures: Vec<_> = report.subdirs.iter().map(|sub| failure(report, sub)).collect();
+        let failed = failures.iter().filter(|f| f.is_some()).count();
+        let suite = escape(&format!("{} vs {}", report.dir_a.display(), report.dir_b.display()));
+
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(out, "<testsuites name=\"dir_compare\" tests=\"{}\" failures=\"{failed}\">", report.subdirs.len())?;
+        writeln!(out, "  <testsuite name=\"{suite}\" tests=\"{}\" failures=\"{failed}\" errors=\"0\">", report.subdirs.len())?;
+        for (sub, failure) in report.subdirs.iter().zip(failures) {
+            let name = escape(&sub.name.to_string_lossy());
+            match failure {
+                None => writeln!(out, "    <testcase classname=\"dir_compare\" name=\"{name}\"/>")?,
+                Some((message, body)) => {
+                    writeln!(out, "    <testcase classname=\"dir_compare\" name=\"{name}\">")?;
+                    writeln!(out, "      <failure message=\"{}\">{}</failure>", escape(&message), escape(&body))?;
+                    writeln!(out, "    </testcase>")?;
+                }
+            }
+        }
+        writeln!(out, "  </testsuite>")?;
+        writeln!(out, "</testsuites>")
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::{self, Write};
+
+use super::Renderer;
+use crate::report::{Category, Report, SubdirReport, SubdirStatus};
+
+/// Writes a Markdown document suitable for merge request comments.
+pub struct MarkdownRenderer;
+
+/// Escape characters that would otherwise be interpreted as Markdown or
+/// break out of a table cell.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' | '\r' => out.push(' '),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn size(size: Option<u64>) -> String {
+    size.map(|s| s.to_string()).unwrap_or_default()
+}
+
+fn write_subdir(out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+    let a = escape(&report.dir_a.display().to_string());
+    let b = escape(&report.dir_b.display().to_string());
+
+    writeln!(out, "### {}\n", escape(&sub.name.to_string_lossy()))?;
+
+    match sub.status {
+        SubdirStatus::OnlyInA => return writeln!(out, "Present in {a} but **missing entirely** in {b}\n"),
+        SubdirStatus::OnlyInB => return writeln!(out, "Present in {b} but **missing entirely** in {a}\n"),
+        SubdirStatus::Compared => {}
+    }
+
+    if sub.is_identical() {
+        if report.hash {
+            return writeln!(out, "✅ identical files and contents\n");
+        }
+        return writeln!(out, "✅ identical file sets (skipped content check)\n");
+    }
+
+    writeln!(out, "| Category | Path | Size A | Size B | Error |")?;
+    writeln!(out, "|---|---|---:|---:|---|")?;
+    for entry in &sub.entries {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            entry.category.as_str(),
+            escape(&entry.path.to_string_lossy()),
+            size(entry.size_a),
+            size(entry.size_b),
+            escape(entry.error.as_deref().unwrap_or("")),
+        )?;
+    }
+    writeln!(out)
+}
+
+impl Renderer for MarkdownRenderer {
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        let a = escape(&report.dir_a.display().to_string());
+        let b = escape(&report.dir_b.display().to_string());
+
+        writeln!(out, "## Directory comparison: {a} vs {b}\n")?;
+
+        writeln!(out, "| | Count |")?;
+        writeln!(out, "|---|---:|")?;
+        writeln!(out, "| Subdirectories | {} |", report.subdirs.len())?;
+        for category in Category::ALL {
+            writeln!(out, "| {} | {} |", category.as_str(), report.count(category))?;
+        }
+        writeln!(out)?;
+
+        for sub in &report.subdirs {
+            write_subdir(out, report, sub)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::report::{DiffEntry, Report, SubdirReport};
+
+pub use text::write_summary;
+
+pub mod anonymize;
+mod brief;
+pub mod content;
+pub mod count;
+mod csv;
+mod dot;
+mod gha;
+mod html;
+mod itemize;
+mod json;
+mod junit;
+mod markdown;
+mod ndjson;
+pub mod print0;
+pub mod split;
+pub mod sqlite;
+mod stat;
+mod tap;
+pub mod template;
+mod text;
+pub mod top;
+mod yaml;
+
+/// Output formats selectable with `--format`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable, ANSI-colored text grouped by subdirectory
+    #[default]
+    Text,
+    /// A single JSON document describing the full comparison
+    Json,
+    /// One JSON object per difference, written as soon as it is found
+    Ndjson,
+    /// One CSV row per difference
+    Csv,
+    /// A single YAML document describing the full comparison
+    Yaml,
+    /// A self-contained HTML page with collapsible subdirectory sections
+    Html,
+    /// A Markdown document with one table per subdirectory
+    Markdown,
+    /// JUnit XML with one test case per subdirectory
+    Junit,
+    /// Test Anything Protocol, one test point per subdirectory
+    Tap,
+    /// GitHub Actions `::error`/`::warning` workflow commands
+    Gha,
+    /// `Only in …` / `Files … differ` lines, like GNU `diff -rq`
+    Brief,
+    /// rsync `--itemize-changes` notation for syncing A onto B
+    Itemize,
+    /// A `git diff --stat`-style histogram per subdirectory
+    Stat,
+    /// A Graphviz digraph of the directories containing differences
+    Dot,
+}
+
+impl Format {
+    /// File extension for reports in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Text | Format::Gha | Format::Brief | Format::Itemize | Format::Stat => "txt",
+            Format::Json => "json",
+            Format::Ndjson => "ndjson",
+            Format::Csv => "csv",
+            Format::Yaml => "yaml",
+            Format::Html => "html",
+            Format::Markdown => "md",
+            Format::Junit => "xml",
+            Format::Tap => "tap",
+            Format::Dot => "dot",
+        }
+    }
+}
+
+/// How the text format lays out the differences in a subdirectory.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum View {
+    /// Sorted path lists grouped by category
+    #[default]
+    Flat,
+    /// An indented tree marking each file with +, -, ~ or !
+    Tree,
+}
+
+/// ANSI escape codes for the human-readable formats, or empty strings when
+/// color is off.
+#[derive(Clone, Copy, Debug)]
+pub struct Palette {
+    pub red: &'static str,
+    pub green: &'static str,
+    pub yellow: &'static str,
+    pub cyan: &'static str,
+    /// Swaps foreground and background, to highlight within a colored line.
+    pub reverse: &'static str,
+    pub reset: &'static str,
+}
+
+impl Palette {
+    pub fn new(color: bool) -> Self {
+        if color {
+            Palette { red: "\x1b[31m", green: "\x1b[32m", yellow: "\x1b[33m", cyan: "\x1b[36m", reverse: "\x1b[7m", reset: "\x1b[0m" }
+        } else {
+            Palette { red: "", green: "", yellow: "", cyan: "", reverse: "", reset: "" }
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::new(false)
+    }
+}
+
+/// When to color the human-readable formats.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolve the choice for output that is (or is not) a terminal.
+    pub fn enabled(self, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        }
+    }
+}
+
+/// Presentation settings shared by the renderers.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Show sizes and modification times on changed-file lines.
+    pub details: bool,
+    pub view: View,
+    /// Leave out subdirectories without differences.
+    pub hide_identical: bool,
+    /// Wrap file paths in OSC 8 terminal hyperlinks.
+    pub hyperlinks: bool,
+    pub palette: Palette,
+    /// Diff the contents of changed text files below their entries.
+    pub content_diff: Option<content::ContentDiff>,
+}
+
+/// Wrap `label` in an OSC 8 hyperlink to the local file `path`.
+pub fn hyperlink(path: &Path, label: &str) -> String {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut url = String::from("file://");
+    for &b in absolute.to_string_lossy().as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => url.push(b as char),
+            _ => url.push_str(&format!("%{b:02X}")),
+        }
+    }
+    format!("\x1b]8;;{url}\x1b\\{label}\x1b]8;;\x1b\\")
+}
+
+/// Turns a [`Report`] into output.
+///
+/// `entry` is called for every difference the moment it is discovered and
+/// `subdir` as soon as each subdirectory has been compared, so formats that
+/// can stream should write there; `finish` is called once with the complete
+/// report.
+pub trait Renderer {
+    fn entry(&mut self, _out: &mut dyn Write, _report: &Report, _sub: &Path, _entry: &DiffEntry) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn subdir(&mut self, _out: &mut dyn Write, _report: &Report, _sub: &SubdirReport) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self, _out: &mut dyn Write, _report: &Report) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn renderer_for(format: Format, options: &Options) -> Box<dyn Renderer> {
+    match format {
+        Format::Text => Box::new(text::TextRenderer::new(options)),
+        Format::Json => Box::new(json::JsonRenderer),
+        Format::Ndjson => Box::new(ndjson::NdjsonRenderer),
+        Format::Csv => Box::new(csv::CsvRenderer::default()),
+        Format::Yaml => Box::new(yaml::YamlRenderer),
+        Format::Html => Box::new(html::HtmlRenderer),
+        Format::Markdown => Box::new(markdown::MarkdownRenderer),
+        Format::Junit => Box::new(junit::JunitRenderer),
+        Format::Tap => Box::new(tap::TapRenderer::default()),
+        Format::Gha => Box::new(gha::GhaRenderer),
+        Format::Brief => Box::new(brief::BriefRenderer::default()),
+        Format::Itemize => Box::new(itemize::ItemizeRenderer::default()),
+        Format::Stat => Box::new(stat::StatRenderer { palette: options.palette }),
+        Format::Dot => Box::new(dot::DotRenderer),
+    }
+}
+
+/// Forwards every call to each of the wrapped renderers in turn.
+pub struct Fanout(pub Vec<Box<dyn Renderer>>);
+
+impl Renderer for Fanout {
+    fn entry(&mut self, out: &mut dyn Write, report: &Report, sub: &Path, entry: &DiffEntry) -> io::Result<()> {
+        self.0.iter_mut().try_for_each(|r| r.entry(out, report, sub, entry))
+    }
+
+    fn subdir(&mut self, out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        self.0.iter_mut().try_for_each(|r| r.subdir(out, report, sub))
+    }
+
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        self.0.iter_mut().try_for_each(|r| r.finish(out, report))
+    }
+}
+
+/// Feed an already complete report through `renderer`.
+pub fn render(renderer: &mut dyn Renderer, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+    for sub in &report.subdirs {
+        for entry in &sub.entries {
+            renderer.entry(out, report, &sub.name, entry)?;
+        }
+        renderer.subdir(out, report, sub)?;
+    }
+    renderer.finish(out, report)
+}
+
+/// Human-readable byte count using decimal units, e.g. `2.3 GB`.
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "kB", "MB", "GB", "TB", "PB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// `n` with thousands separators, e.g. `1,234`.
+pub fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// `bytes` processed in `secs` as a rate such as `350.2 MB/s`.
+pub fn human_rate(bytes: u64, secs: f64) -> String {
+    if secs > 0.0 {
+        format!("{}/s", human_bytes((bytes as f64 / secs) as u64))
+    } else {
+        "-".to_string()
+    }
+}
+
+/// Signed human-readable byte count, e.g. `+2.3 GB` or `-512 B`.
+pub fn human_delta(delta: i64) -> String {
+    match delta {
+        0 => "0 B".to_string(),
+        d if d > 0 => format!("+{}", human_bytes(d as u64)),
+        d => format!("-{}", human_bytes(d.unsigned_abs())),
+    }
+}
+
+/// Escape text for inclusion in HTML/XML element content or attribute values.
+fn escape_markup(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,40 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::Renderer;
+use crate::report::{lossy_path, DiffEntry, Report, SubdirReport, SubdirStatus};
+
+/// Streams one JSON object per line, without buffering the report.
+pub struct NdjsonRenderer;
+
+#[derive(Serialize)]
+struct Line<'a, T: Serialize> {
+    #[serde(serialize_with = "lossy_path")]
+    subdir: &'a Path,
+    #[serde(flatten)]
+    record: &'a T,
+}
+
+impl Renderer for NdjsonRenderer {
+    fn entry(&mut self, out: &mut dyn Write, _report: &Report, sub: &Path, entry: &DiffEntry) -> io::Result<()> {
+        serde_json::to_writer(&mut *out, &Line { subdir: sub, record: entry })?;
+        writeln!(out)?;
+        out.flush()
+    }
+
+    fn subdir(&mut self, out: &mut dyn Write, _report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        // Subdirectories missing on one side have no entries of their own.
+        if sub.status == SubdirStatus::Compared {
+            return Ok(());
+        }
+        #[derive(Serialize)]
+        struct Status {
+            status: SubdirStatus,
+        }
+        serde_json::to_writer(&mut *out, &Line { subdir: &sub.name, record: &Status { status: sub.status } })?;
+        writeln!(out)?;
+        out.flush()
+    }
+}
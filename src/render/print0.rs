@@ -0,0 +1,27 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::Renderer;
+use crate::report::{DiffEntry, Report, SubdirReport, SubdirStatus};
+
+/// Streams the relative path of every difference, each terminated by a NUL
+/// byte, for `xargs -0`. Paths are written as raw bytes, not lossily decoded.
+pub struct Print0Renderer;
+
+fn write_path(out: &mut dyn Write, path: &Path) -> io::Result<()> {
+    out.write_all(path.as_os_str().as_encoded_bytes())?;
+    out.write_all(b"\0")
+}
+
+impl Renderer for Print0Renderer {
+    fn entry(&mut self, out: &mut dyn Write, _report: &Report, sub: &Path, entry: &DiffEntry) -> io::Result<()> {
+        write_path(out, &sub.join(&entry.path))
+    }
+
+    fn subdir(&mut self, out: &mut dyn Write, _report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        match sub.status {
+            SubdirStatus::OnlyInA | SubdirStatus::OnlyInB => write_path(out, &sub.name),
+            SubdirStatus::Compared => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::{escape_markup, render, Renderer};
+use crate::report::{Category, Report, SubdirReport};
+
+/// Writes one report file per subdirectory into `subdirs/` below a
+/// directory, plus an index listing them.
+pub struct SplitRenderer {
+    dir: PathBuf,
+    extension: &'static str,
+    make: Box<dyn Fn() -> Box<dyn Renderer>>,
+    written: Vec<PathBuf>,
+}
+
+impl SplitRenderer {
+    /// `make` creates a fresh renderer for each report file.
+    pub fn create(dir: &Path, extension: &'static str, make: Box<dyn Fn() -> Box<dyn Renderer>>) -> io::Result<Self> {
+        fs::create_dir_all(dir.join("subdirs"))?;
+        Ok(SplitRenderer { dir: dir.to_path_buf(), extension, make, written: Vec::new() })
+    }
+
+    fn write_index(&self, report: &Report) -> io::Result<PathBuf> {
+        let html = self.extension == "html";
+        let index = self.dir.join(if html { "index.html" } else { "index.txt" });
+        let mut out = BufWriter::new(File::create(&index)?);
+
+        if html {
+            writeln!(out, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+            writeln!(out, "<title>dir_compare index</title>\n</head>\n<body>\n<table>")?;
+            write!(out, "<tr><th>Subdirectory</th><th>Status</th>")?;
+            for category in Category::ALL {
+                write!(out, "<th>{}</th>", category.as_str())?;
+            }
+            writeln!(out, "</tr>")?;
+        } else {
+            let header: Vec<_> = Category::ALL.iter().map(|c| c.as_str()).collect();
+            writeln!(out, "file\tstatus\t{}", header.join("\t"))?;
+        }
+
+        for (sub, file) in report.subdirs.iter().zip(&self.written) {
+            let rel = file.strip_prefix(&self.dir).unwrap_or(file).to_string_lossy();
+            let counts: Vec<_> = Category::ALL.iter().map(|&c| sub.count(c).to_string()).collect();
+            if html {
+                let rel = escape_markup(&rel);
+                let name = escape_markup(&sub.name.to_string_lossy());
+                write!(out, "<tr><td><a href=\"{rel}\">{name}</a></td><td>{}</td>", sub.status.as_str())?;
+                for count in counts {
+                    write!(out, "<td>{count}</td>")?;
+                }
+                writeln!(out, "</tr>")?;
+            } else {
+                writeln!(out, "{rel}\t{}\t{}", sub.status.as_str(), counts.join("\t"))?;
+            }
+        }
+
+        if html {
+            writeln!(out, "</table>\n</body>\n</html>")?;
+        }
+        out.flush()?;
+        Ok(index)
+    }
+}
+
+impl Renderer for SplitRenderer {
+    fn subdir(&mut self, _out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        // The roots themselves (`--flat`, verification) have no name of their own.
+        let name = sub.name.file_name().map_or("root".into(), |name| name.to_string_lossy());
+        let path = self.dir.join("subdirs").join(format!("{name}.{}", self.extension));
+        let single = Report {
+            content: report.content,
+            algo: report.algo,
+            mtime: report.mtime,
+            subdirs: vec![sub.clone()],
+            elapsed_secs: report.elapsed_secs,
+            ..Report::new(report.dir_a.clone(), report.dir_b.clone(), report.hash)
+        };
+
+        let mut file = BufWriter::new(File::create(&path)?);
+        render((self.make)().as_mut(), &mut file, &single)?;
+        file.flush()?;
+        self.written.push(path);
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        let index = self.write_index(report)?;
+        writeln!(out, "Wrote {} reports to {}, index in {}", self.written.len(), self.dir.display(), index.display())
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection};
+
+use super::Renderer;
+use crate::report::{DiffEntry, Report, SubdirReport};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id          INTEGER PRIMARY KEY,
+    started_at  INTEGER NOT NULL,  -- seconds since the Unix epoch
+    finished_at INTEGER,
+    dir_a       TEXT NOT NULL,
+    dir_b       TEXT NOT NULL,
+    hash        INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS subdirs (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    name   TEXT NOT NULL,
+    status TEXT NOT NULL,
+    PRIMARY KEY (run_id, name)
+);
+CREATE TABLE IF NOT EXISTS entries (
+    id       INTEGER PRIMARY KEY,
+    run_id   INTEGER NOT NULL REFERENCES runs(id),
+    subdir   TEXT NOT NULL,
+    category TEXT NOT NULL,
+    path     TEXT NOT NULL,
+    size_a   INTEGER,
+    size_b   INTEGER,
+    digest_a TEXT,
+    digest_b TEXT,
+    error    TEXT
+);
+CREATE INDEX IF NOT EXISTS entries_run_category ON entries(run_id, category);
+";
+
+/// Records the run, its subdirectories and every difference into an SQLite
+/// database. The whole run is written in a single transaction.
+pub struct SqliteRecorder {
+    conn: Connection,
+    started_at: i64,
+    run_id: Option<i64>,
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default()
+}
+
+impl SqliteRecorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let conn = Connection::open(path).map_err(io::Error::other)?;
+        conn.execute_batch(SCHEMA).map_err(io::Error::other)?;
+        conn.execute_batch("BEGIN").map_err(io::Error::other)?;
+        Ok(SqliteRecorder { conn, started_at: now(), run_id: None })
+    }
+
+    /// The id of this run's row, inserting it on first use.
+    fn run_id(&mut self, report: &Report) -> rusqlite::Result<i64> {
+        if let Some(id) = self.run_id {
+            return Ok(id);
+        }
+        self.conn.execute(
+            "INSERT INTO runs (started_at, dir_a, dir_b, hash) VALUES (?1, ?2, ?3, ?4)",
+            params![self.started_at, report.dir_a.to_string_lossy(), report.dir_b.to_string_lossy(), report.hash],
+        )?;
+        let id = self.conn.last_insert_rowid();
+        self.run_id = Some(id);
+        Ok(id)
+    }
+
+    fn record_entry(&mut self, report: &Report, sub: &Path, entry: &DiffEntry) -> rusqlite::Result<()> {
+        let run_id = self.run_id(report)?;
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT INTO entries (run_id, subdir, category, path, size_a, size_b, digest_a, digest_b, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )?;
+        stmt.execute(params![
+            run_id,
+            sub.to_string_lossy(),
+            entry.category.as_str(),
+            entry.path.to_string_lossy(),
+            // SQLite integers are signed 64-bit.
+            entry.size_a.map(|s| s as i64),
+            entry.size_b.map(|s| s as i64),
+            entry.digest_a,
+            entry.digest_b,
+            entry.error,
+        ])?;
+        Ok(())
+    }
+
+    fn record_subdir(&mut self, report: &Report, sub: &SubdirReport) -> rusqlite::Result<()> {
+        let run_id = self.run_id(report)?;
+        let mut stmt = self.conn.prepare_cached("INSERT INTO subdirs (run_id, name, status) VALUES (?1, ?2, ?3)")?;
+        stmt.execute(params![run_id, sub.name.to_string_lossy(), sub.status.as_str()])?;
+        Ok(())
+    }
+
+    fn record_finish(&mut self, report: &Report) -> rusqlite::Result<()> {
+        let run_id = self.run_id(report)?;
+        self.conn.execute("UPDATE runs SET finished_at = ?1 WHERE id = ?2", params![now(), run_id])?;
+        self.conn.execute_batch("COMMIT")
+    }
+}
+
+impl Renderer for SqliteRecorder {
+    fn entry(&mut self, _out: &mut dyn Write, report: &Report, sub: &Path, entry: &DiffEntry) -> io::Result<()> {
+        self.record_entry(report, sub, entry).map_err(io::Error::other)
+    }
+
+    fn subdir(&mut self, _out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        self.record_subdir(report, sub).map_err(io::Error::other)
+    }
+
+    fn finish(&mut self, _out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        self.record_finish(report).map_err(io::Error::other)
+    }
+}
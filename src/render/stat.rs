@@ -0,0 +1,97 @@
+use std::io::{self, Write};
+
+use super::{human_delta, Palette, Renderer};
+use crate::report::{Category, Report, SubdirReport, SubdirStatus};
+
+/// Widest bar drawn for the subdirectory with the most differences.
+const BAR_WIDTH: usize = 50;
+
+/// A `git diff --stat`-style histogram: one line per subdirectory with a bar
+/// of the files only in A (`+`), only in B (`-`) and changed, and the size
+/// delta of A over B, as in the text report's rollups.
+pub struct StatRenderer {
+    pub palette: Palette,
+}
+
+/// (only in A, only in B, changed) file counts for a subdirectory.
+fn counts(sub: &SubdirReport) -> (usize, usize, usize) {
+    match sub.status {
+        SubdirStatus::OnlyInA => (sub.stats.files_a, 0, 0),
+        SubdirStatus::OnlyInB => (0, sub.stats.files_b, 0),
+        SubdirStatus::Compared => (
+            sub.count(Category::MissingInB),
+            sub.count(Category::MissingInA),
+            sub.count(Category::TypeConflict) + sub.count(Category::Changed) + sub.count(Category::Metadata) + sub.count(Category::Errored),
+        ),
+    }
+}
+
+/// Scale `n` to the bar, keeping at least one character for non-zero counts.
+fn scaled(n: usize, max: usize) -> usize {
+    if max <= BAR_WIDTH || n == 0 {
+        return n;
+    }
+    (n * BAR_WIDTH / max).max(1)
+}
+
+impl Renderer for StatRenderer {
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        let Palette { red, green, yellow, reset, .. } = self.palette;
+        let rows: Vec<_> = report
+            .subdirs
+            .iter()
+            .map(|sub| (sub.name.display().to_string(), counts(sub), sub.size_delta()))
+            .filter(|(_, (a, b, c), _)| a + b + c > 0)
+            .collect();
+
+        let name_width = rows.iter().map(|(name, _, _)| name.chars().count()).max().unwrap_or(0);
+        let max = rows.iter().map(|(_, (a, b, c), _)| a + b + c).max().unwrap_or(0);
+        let count_width = max.to_string().len();
+
+        let (mut only_a, mut only_b, mut changed, mut delta) = (0, 0, 0, 0);
+        for (name, (a, b, c), d) in &rows {
+            writeln!(
+                out,
+                " {name:<name_width$} | {:>count_width$} {green}{}{red}{}{yellow}{}{reset} ({})",
+                a + b + c,
+                "+".repeat(scaled(*a, max)),
+                "-".repeat(scaled(*b, max)),
+                "~".repeat(scaled(*c, max)),
+                human_delta(*d),
+            )?;
+            delta += d;
+            only_a += a;
+            only_b += b;
+            changed += c;
+        }
+
+        writeln!(
+            out,
+            " {} of {} subdirectories differ, {only_a} files only in A(+), {only_b} only in B(-), {changed} changed(~), {}",
+            rows.len(),
+            report.subdirs.len(),
+            human_delta(delta)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Stats;
+
+    #[test]
+    fn signs_match_the_text_rollup() {
+        let only_a = SubdirReport::new("x".into(), SubdirStatus::OnlyInA, Vec::new(), Stats { files_a: 1, bytes_a: 7, ..Stats::default() });
+        let only_b = SubdirReport::new("y".into(), SubdirStatus::OnlyInB, Vec::new(), Stats { files_b: 1, bytes_b: 5, ..Stats::default() });
+        let mut report = Report::new("a".into(), "b".into(), false);
+        report.subdirs = vec![only_a, only_b];
+        let mut out = Vec::new();
+        StatRenderer { palette: Palette::new(false) }.finish(&mut out, &report).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines[0], " x | 1 + (+7 B)");
+        assert_eq!(lines[1], " y | 1 - (-5 B)");
+        assert!(lines[2].ends_with("1 files only in A(+), 1 only in B(-), 0 changed(~), +2 B"));
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Write};
+
+use serde_yaml::{Mapping, Value};
+
+use super::Renderer;
+use crate::report::{Category, Report, SubdirReport, SubdirStatus};
+
+/// Streams TAP version 13: one test point per subdirectory, with a YAML
+/// diagnostic block listing the differing paths. The plan is printed last.
+#[derive(Default)]
+pub struct TapRenderer {
+    tests: usize,
+}
+
+/// YAML diagnostics for a failed subdirectory.
+fn diagnostics(report: &Report, sub: &SubdirReport) -> Mapping {
+    let mut diag = Mapping::new();
+    match sub.status {
+        SubdirStatus::OnlyInA => {
+            let message = format!("Present in {} but MISSING entirely in {}", report.dir_a.display(), report.dir_b.display());
+            diag.insert("message".into(), message.into());
+        }
+        SubdirStatus::OnlyInB => {
+            let message = format!("Present in {} but MISSING entirely in {}", report.dir_b.display(), report.dir_a.display());
+            diag.insert("message".into(), message.into());
+        }
+        SubdirStatus::Compared => {
+            for category in Category::ALL {
+                let paths: Vec<Value> = sub
+                    .entries_in(category)
+                    .map(|e| match &e.error {
+                        Some(err) => format!("{} ({err})", e.path.display()).into(),
+                        None => e.path.display().to_string().into(),
+                    })
+                    .collect();
+                if !paths.is_empty() {
+                    diag.insert(category.as_str().into(), paths.into());
+                }
+            }
+        }
+    }
+    diag
+}
+
+impl Renderer for TapRenderer {
+    fn subdir(&mut self, out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        if self.tests == 0 {
+            writeln!(out, "TAP version 13")?;
+        }
+        self.tests += 1;
+        // '#' starts a directive in TAP, so it must not appear unescaped in a description.
+        let name = sub.name.to_string_lossy().replace('#', "\\#");
+
+        if sub.is_identical() {
+            return writeln!(out, "ok {} - {name}", self.tests);
+        }
+
+        writeln!(out, "not ok {} - {name}", self.tests)?;
+        let yaml = serde_yaml::to_string(&diagnostics(report, sub)).map_err(io::Error::other)?;
+        writeln!(out, "  ---")?;
+        for line in yaml.lines() {
+            writeln!(out, "  {line}")?;
+        }
+        writeln!(out, "  ...")
+    }
+
+    fn finish(&mut self, out: &mut dyn Write, _report: &Report) -> io::Result<()> {
+        if self.tests == 0 {
+            writeln!(out, "TAP version 13")?;
+        }
+        writeln!(out, "1..{}", self.tests)
+    }
+}
@@ -0,0 +1,119 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::Renderer;
+use crate::report::{rfc3339, DiffEntry, Report, SubdirReport, SubdirStatus};
+
+/// Placeholders usable in a template, e.g. `{category}\t{path}`.
+const FIELDS: [&str; 14] = [
+    "category", "subdir", "path", "full_path", "size_a", "size_b", "digest_a", "digest_b", "mtime_a", "mtime_b", "newer",
+    "error", "dir_a", "dir_b",
+];
+
+#[derive(Clone)]
+enum Segment {
+    Literal(String),
+    Field(&'static str),
+}
+
+/// Streams one line per difference, formatted from a user-supplied template.
+#[derive(Clone)]
+pub struct TemplateRenderer {
+    segments: Vec<Segment>,
+}
+
+impl TemplateRenderer {
+    /// Parse a template. `{name}` is replaced by a field, `{{`/`}}` produce
+    /// literal braces and `\t`, `\n`, `\0`, `\\` are unescaped.
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('0') => literal.push('\0'),
+                    Some(other) => literal.push(other),
+                    None => literal.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    let field = FIELDS
+                        .iter()
+                        .find(|&&f| f == name)
+                        .ok_or_else(|| format!("unknown template field {{{name}}}; available: {}", FIELDS.join(", ")))?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(TemplateRenderer { segments })
+    }
+
+    fn write_line(&self, out: &mut dyn Write, value: &dyn Fn(&str) -> String) -> io::Result<()> {
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => out.write_all(text.as_bytes())?,
+                Segment::Field(name) => out.write_all(value(name).as_bytes())?,
+            }
+        }
+        writeln!(out)
+    }
+}
+
+fn opt<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+}
+
+impl Renderer for TemplateRenderer {
+    fn entry(&mut self, out: &mut dyn Write, report: &Report, sub: &Path, entry: &DiffEntry) -> io::Result<()> {
+        self.write_line(out, &|field| match field {
+            "category" => entry.category.as_str().to_string(),
+            "subdir" => sub.display().to_string(),
+            "path" => entry.path.display().to_string(),
+            "full_path" => sub.join(&entry.path).display().to_string(),
+            "size_a" => opt(&entry.size_a),
+            "size_b" => opt(&entry.size_b),
+            "digest_a" => opt(&entry.digest_a),
+            "digest_b" => opt(&entry.digest_b),
+            "mtime_a" => entry.mtime_a.map(rfc3339).unwrap_or_default(),
+            "mtime_b" => entry.mtime_b.map(rfc3339).unwrap_or_default(),
+            "newer" => entry.newer(report.mtime).map(|n| n.as_str().to_string()).unwrap_or_default(),
+            "error" => opt(&entry.error),
+            "dir_a" => report.dir_a.display().to_string(),
+            "dir_b" => report.dir_b.display().to_string(),
+            _ => String::new(),
+        })
+    }
+
+    fn subdir(&mut self, out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        // Subdirectories missing on one side are reported as a single line.
+        if sub.status == SubdirStatus::Compared {
+            return Ok(());
+        }
+        self.write_line(out, &|field| match field {
+            "category" => sub.status.as_str().to_string(),
+            "subdir" | "full_path" => sub.name.display().to_string(),
+            "dir_a" => report.dir_a.display().to_string(),
+            "dir_b" => report.dir_b.display().to_string(),
+            _ => String::new(),
+        })
+    }
+}
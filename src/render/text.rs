@@ -0,0 +1,366 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::content::ContentDiff;
+use super::{group_digits, human_bytes, human_delta, human_rate, hyperlink, Options, Palette, Renderer, View};
+use crate::compare::ContentCheck;
+use crate::report::{rfc3339, Category, DiffEntry, MtimeMatch, Report, SubdirReport, SubdirStatus};
+
+pub struct TextRenderer {
+    details: bool,
+    view: View,
+    hide_identical: bool,
+    hyperlinks: bool,
+    palette: Palette,
+    /// The A and B side of the subdirectory being written, for hyperlinks.
+    roots: (PathBuf, PathBuf),
+    /// The report's tolerance for the newer-side annotation.
+    mtime: MtimeMatch,
+    /// Diffs shown below changed text files.
+    content_diff: Option<ContentDiff>,
+}
+
+impl TextRenderer {
+    pub fn new(options: &Options) -> Self {
+        TextRenderer {
+            details: options.details,
+            view: options.view,
+            hide_identical: options.hide_identical,
+            hyperlinks: options.hyperlinks,
+            palette: options.palette,
+            roots: Default::default(),
+            mtime: MtimeMatch::default(),
+            content_diff: options.content_diff,
+        }
+    }
+
+    /// `label`, linked to `entry` on the side it exists on (A for files on both).
+    fn link(&self, entry: &DiffEntry, label: &str) -> String {
+        if !self.hyperlinks {
+            return label.to_string();
+        }
+        let root = if entry.category == Category::MissingInA { &self.roots.1 } else { &self.roots.0 };
+        hyperlink(&root.join(&entry.path), label)
+    }
+
+    /// One line per difference, grouped under a heading per category.
+    fn write_flat(&self, out: &mut dyn Write, sub: &SubdirReport, a: &str, b: &str) -> io::Result<()> {
+        let Palette { yellow, reset, .. } = self.palette;
+        for category in Category::ALL.into_iter().chain([Category::Same]) {
+            let mut entries = sub.entries_in(category).peekable();
+            let omitted = sub.omitted.get(&category).copied().unwrap_or(0);
+            if entries.peek().is_none() && omitted == 0 {
+                continue;
+            }
+            writeln!(out, "  {yellow}{}:{reset}", category.label(a, b))?;
+            for entry in entries {
+                write!(out, "    ")?;
+                self.write_entry(out, entry, &entry.path.display().to_string())?;
+            }
+            if omitted > 0 {
+                writeln!(out, "    …and {} more", group_digits(omitted))?;
+            }
+        }
+        self.write_stopped(out, sub)
+    }
+
+    /// Notes for the differences `--max-diffs` left out of the listing.
+    fn write_omitted(&self, out: &mut dyn Write, sub: &SubdirReport) -> io::Result<()> {
+        for (category, omitted) in &sub.omitted {
+            writeln!(out, "  …and {} more {}", group_digits(*omitted), category.as_str())?;
+        }
+        self.write_stopped(out, sub)
+    }
+
+    fn write_stopped(&self, out: &mut dyn Write, sub: &SubdirReport) -> io::Result<()> {
+        if sub.stopped {
+            let Palette { yellow, reset, .. } = self.palette;
+            writeln!(out, "  {yellow}Comparison stopped early; more files may differ{reset}")?;
+        }
+        Ok(())
+    }
+
+    /// One line per difference in path order, marked like the tree view.
+    fn write_sorted(&self, out: &mut dyn Write, sub: &SubdirReport) -> io::Result<()> {
+        let reset = self.palette.reset;
+        for entry in &sub.entries {
+            let color = marker_color(&self.palette, entry.category);
+            write!(out, "{color}{}{reset} ", marker(entry.category))?;
+            self.write_entry(out, entry, &entry.path.display().to_string())?;
+        }
+        self.write_omitted(out, sub)
+    }
+
+    /// The differences as an indented tree of their paths.
+    fn write_tree(&self, out: &mut dyn Write, sub: &SubdirReport) -> io::Result<()> {
+        let mut root = TreeNode::default();
+        for entry in &sub.entries {
+            let mut node = &mut root;
+            for component in entry.path.iter() {
+                node = node.children.entry(component.to_string_lossy().into_owned()).or_default();
+            }
+            node.entry = Some(entry);
+        }
+        self.write_nodes(out, &root, "  ")?;
+        self.write_omitted(out, sub)
+    }
+
+    fn write_nodes(&self, out: &mut dyn Write, node: &TreeNode, prefix: &str) -> io::Result<()> {
+        let reset = self.palette.reset;
+        let count = node.children.len();
+        for (i, (name, child)) in node.children.iter().enumerate() {
+            let last = i + 1 == count;
+            write!(out, "{prefix}{}", if last { "└── " } else { "├── " })?;
+            match child.entry {
+                Some(entry) => {
+                    let color = marker_color(&self.palette, entry.category);
+                    write!(out, "{color}{}{reset} ", marker(entry.category))?;
+                    self.write_entry(out, entry, name)?;
+                }
+                None => writeln!(out, "{name}/")?,
+            }
+            self.write_nodes(out, child, &format!("{prefix}{}", if last { "    " } else { "│   " }))?;
+        }
+        Ok(())
+    }
+
+    /// Write `label` for `entry`, followed by its annotations and a newline.
+    fn write_entry(&self, out: &mut dyn Write, entry: &DiffEntry, label: &str) -> io::Result<()> {
+        let Palette { red, green, reset, .. } = self.palette;
+        let label = &self.link(entry, label);
+        if let Some(files) = entry.files {
+            let side = if entry.category == Category::MissingInB { "A" } else { "B" };
+            return writeln!(out, "{red}{}{reset} only in {side}", dir_summary(label, files, entry.size_a.or(entry.size_b)));
+        }
+        let color = if entry.category == Category::Same { green } else { red };
+        match (&entry.error, entry.newer(self.mtime)) {
+            (Some(e), _) => write!(out, "{color}{label} — {e}{reset}")?,
+            (None, Some(newer)) => write!(out, "{color}{label}{reset} ({})", newer.as_str())?,
+            (None, None) => write!(out, "{color}{label}{reset}")?,
+        }
+        if matches!(entry.category, Category::DanglingInA | Category::DanglingInB) {
+            write!(out, " -> {}", entry.target().unwrap_or("?"))?;
+        } else if !entry.attrs.is_empty() {
+            write!(out, " {}", attrs(entry))?;
+        }
+        if self.details && entry.category == Category::Changed {
+            write!(out, " {}", details(entry))?;
+        }
+        if entry.unchecked {
+            write!(out, " (skipped content check)")?;
+        }
+        writeln!(out)?;
+        match self.content_diff {
+            Some(_) if entry.category == Category::Changed && entry.binary == Some(true) => {
+                let size = |size: Option<u64>| size.map(human_bytes).unwrap_or_else(|| "?".to_string());
+                writeln!(out, "      binary files differ (sizes {} vs {})", size(entry.size_a), size(entry.size_b))
+            }
+            Some(diff) if entry.category == Category::Changed => {
+                diff.write(out, &self.roots.0.join(&entry.path), &self.roots.1.join(&entry.path), self.palette)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A directory level of the tree view; leaves carry the difference.
+#[derive(Default)]
+struct TreeNode<'a> {
+    children: BTreeMap<String, TreeNode<'a>>,
+    entry: Option<&'a DiffEntry>,
+}
+
+fn marker_color(palette: &Palette, category: Category) -> &'static str {
+    match category {
+        Category::Errored => palette.red,
+        Category::Same => palette.green,
+        _ => palette.yellow,
+    }
+}
+
+/// Tree-view marker, matching the header rollup.
+fn marker(category: Category) -> char {
+    match category {
+        Category::MissingInB => '+',
+        Category::MissingInA => '-',
+        Category::TypeConflict => '*',
+        Category::Changed => '~',
+        Category::Metadata => '^',
+        Category::DanglingInA | Category::DanglingInB => '@',
+        Category::Errored => '!',
+        Category::Same => '=',
+    }
+}
+
+/// `dir/ (1,234 files, 2.3 GB)` for a directory present on one side only.
+fn dir_summary(name: &str, files: usize, bytes: Option<u64>) -> String {
+    let noun = if files == 1 { "file" } else { "files" };
+    format!("{name}/ ({} {noun}, {})", group_digits(files), human_bytes(bytes.unwrap_or(0)))
+}
+
+/// `[A: size, mtime | B: size, mtime]` for `--details`.
+fn details(entry: &DiffEntry) -> String {
+    let side = |size: Option<u64>, mtime: Option<SystemTime>| {
+        let size = size.map(human_bytes).unwrap_or_else(|| "?".to_string());
+        let mtime = mtime.map(rfc3339).unwrap_or_else(|| "?".to_string());
+        format!("{size}, {mtime}")
+    };
+    format!("[A: {} | B: {}]", side(entry.size_a, entry.mtime_a), side(entry.size_b, entry.mtime_b))
+}
+
+/// `[mode: A 644 | B 600; ...]` for the differing metadata of `entry`.
+fn attrs(entry: &DiffEntry) -> String {
+    let value = |v: &Option<String>| v.clone().unwrap_or_else(|| "none".to_string());
+    let attrs: Vec<_> = entry.attrs.iter().map(|a| format!("{}: A {} | B {}", a.name, value(&a.a), value(&a.b))).collect();
+    format!("[{}]", attrs.join("; "))
+}
+
+/// Rollup for the subdirectory header: `(+only in A -only in B ~changed)`,
+/// plus `*type conflicts`, `^metadata`, `@dangling` and `!errored` when
+/// there are any, and the net size delta.
+fn rollup(sub: &SubdirReport) -> String {
+    let mut counts = format!(
+        "(+{} -{} ~{}",
+        sub.count(Category::MissingInB),
+        sub.count(Category::MissingInA),
+        sub.count(Category::Changed)
+    );
+    let conflicts = sub.count(Category::TypeConflict);
+    if conflicts > 0 {
+        counts.push_str(&format!(" *{conflicts}"));
+    }
+    let metadata = sub.count(Category::Metadata);
+    if metadata > 0 {
+        counts.push_str(&format!(" ^{metadata}"));
+    }
+    let dangling = sub.count(Category::DanglingInA) + sub.count(Category::DanglingInB);
+    if dangling > 0 {
+        counts.push_str(&format!(" @{dangling}"));
+    }
+    let errored = sub.count(Category::Errored);
+    if errored > 0 {
+        counts.push_str(&format!(" !{errored}"));
+    }
+    counts.push_str(&format!(", {})", human_delta(sub.size_delta())));
+    counts
+}
+
+impl Renderer for TextRenderer {
+    fn subdir(&mut self, out: &mut dyn Write, report: &Report, sub: &SubdirReport) -> io::Result<()> {
+        let Palette { red, green, cyan, reset, .. } = self.palette;
+        self.roots = report.subdir_paths(sub);
+        self.mtime = report.mtime;
+        let a = report.dir_a.display();
+        let b = report.dir_b.display();
+
+        // `--flat`: the roots were compared as a whole, so skip the sectioning.
+        if sub.name.as_os_str().is_empty() {
+            return self.write_sorted(out, sub);
+        }
+
+        if self.hide_identical && sub.is_identical() {
+            return Ok(());
+        }
+
+        if sub.status == SubdirStatus::Compared && !sub.is_identical() {
+            writeln!(out, "\n{cyan}=== Subdirectory: {} {} ==={reset}", sub.name.display(), rollup(sub))?;
+        } else if sub.status != SubdirStatus::Compared {
+            writeln!(out, "\n{cyan}=== Subdirectory: {} ({}) ==={reset}", sub.name.display(), human_delta(sub.size_delta()))?;
+        } else {
+            writeln!(out, "\n{cyan}=== Subdirectory: {} ==={reset}", sub.name.display())?;
+        }
+
+        match sub.status {
+            SubdirStatus::OnlyInA => {
+                let what = dir_summary(&sub.name.display().to_string(), sub.stats.files_a, Some(sub.stats.bytes_a));
+                return writeln!(out, "  {red}Present in {a} but MISSING entirely in {b}{reset}: {what}");
+            }
+            SubdirStatus::OnlyInB => {
+                let what = dir_summary(&sub.name.display().to_string(), sub.stats.files_b, Some(sub.stats.bytes_b));
+                return writeln!(out, "  {red}Present in {b} but MISSING entirely in {a}{reset}: {what}");
+            }
+            SubdirStatus::Compared => {}
+        }
+
+        let (path_a, path_b) = report.subdir_paths(sub);
+        let a = path_a.display().to_string();
+        let b = path_b.display().to_string();
+
+        if sub.is_identical() {
+            if report.hash {
+                writeln!(out, "  {green}✅ identical files and contents{reset}")?;
+            } else {
+                writeln!(out, "  {green}✅ identical file sets (skipped content check){reset}")?;
+            }
+        }
+
+        match self.view {
+            View::Flat => self.write_flat(out, sub, &a, &b),
+            View::Tree => self.write_tree(out, sub),
+        }
+    }
+
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        let Palette { cyan, yellow, reset, .. } = self.palette;
+        let stats = report.stats();
+        let only_a = report.subdirs.iter().filter(|s| s.status == SubdirStatus::OnlyInA).count();
+        let only_b = report.subdirs.iter().filter(|s| s.status == SubdirStatus::OnlyInB).count();
+
+        writeln!(out, "\n{cyan}=== Summary ==={reset}")?;
+        writeln!(out, "  Files scanned:   {} in {}, {} in {}", stats.files_a, report.dir_a.display(), stats.files_b, report.dir_b.display())?;
+        writeln!(out, "  Subdirectories:  {} ({only_a} only in A, {only_b} only in B)", report.subdirs.len())?;
+        writeln!(out, "  Missing in B:    {}", report.count(Category::MissingInB))?;
+        writeln!(out, "  Missing in A:    {}", report.count(Category::MissingInA))?;
+        writeln!(out, "  Size delta:      {} (A over B)", human_delta(report.subdirs.iter().map(SubdirReport::size_delta).sum()))?;
+        if report.hash {
+            writeln!(out, "  Changed:         {}", report.count(Category::Changed))?;
+            writeln!(out, "  Errored:         {}", report.count(Category::Errored))?;
+        }
+        let conflicts = report.count(Category::TypeConflict);
+        if conflicts > 0 {
+            writeln!(out, "  Type conflicts:  {conflicts}")?;
+        }
+        let metadata = report.count(Category::Metadata);
+        if metadata > 0 {
+            writeln!(out, "  Metadata:        {metadata}")?;
+        }
+        let dangling = (report.count(Category::DanglingInA), report.count(Category::DanglingInB));
+        if dangling != (0, 0) {
+            writeln!(out, "  Dangling links:  {} in A, {} in B", dangling.0, dangling.1)?;
+        }
+        if report.hash {
+            writeln!(
+                out,
+                "  Bytes read:      {} ({}, {})",
+                human_bytes(stats.bytes_hashed),
+                human_rate(stats.bytes_hashed, stats.hash_secs),
+                report.method()
+            )?;
+            if stats.unchecked > 0 {
+                writeln!(out, "  Skipped content: {} files over --hash-max-size, compared by size and mtime", stats.unchecked)?;
+            }
+            if let Some(warning) = report.algo.collision_warning().filter(|_| report.content == ContentCheck::Hash) {
+                writeln!(out, "  {yellow}Note:{reset}            {warning}")?;
+            }
+        }
+        writeln!(out, "  Elapsed:         {:.2}s", report.elapsed_secs)
+    }
+}
+
+/// One-line overview printed to the terminal when the report itself was
+/// written to `path`.
+pub fn write_summary(out: &mut dyn Write, report: &Report, path: &Path, palette: Palette) -> io::Result<()> {
+    let Palette { green, yellow, reset, .. } = palette;
+    let counts: Vec<String> = Category::ALL.iter().map(|&c| format!("{} {}", report.count(c), c.as_str())).collect();
+    let missing_subdirs = report.subdirs.iter().filter(|s| s.status != SubdirStatus::Compared).count();
+    let color = if report.subdirs.iter().all(SubdirReport::is_identical) { green } else { yellow };
+    writeln!(
+        out,
+        "{color}Report written to {}{reset}: {} subdirectories ({missing_subdirs} on one side only), {}",
+        path.display(),
+        report.subdirs.len(),
+        counts.join(", ")
+    )
+}
@@ -0,0 +1,44 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use super::{human_bytes, Renderer};
+use crate::report::{Category, Report, SubdirStatus};
+
+/// Lists the largest missing or changed files across the whole comparison.
+pub struct TopRenderer {
+    n: usize,
+}
+
+impl TopRenderer {
+    pub fn new(n: usize) -> Self {
+        TopRenderer { n }
+    }
+}
+
+impl Renderer for TopRenderer {
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        let mut files: Vec<(u64, Category, PathBuf)> = report
+            .subdirs
+            .iter()
+            .flat_map(|sub| sub.entries.iter().map(move |e| (sub, e)))
+            .filter(|(_, e)| matches!(e.category, Category::MissingInA | Category::MissingInB | Category::Changed))
+            .map(|(sub, e)| (e.size_a.max(e.size_b).unwrap_or(0), e.category, sub.name.join(&e.path)))
+            .collect();
+        // Subdirectories present on one side only are missing as a whole.
+        files.extend(report.subdirs.iter().filter_map(|sub| {
+            let name = PathBuf::from(format!("{}/", sub.name.display()));
+            match sub.status {
+                SubdirStatus::OnlyInA => Some((sub.stats.bytes_a, Category::MissingInB, name)),
+                SubdirStatus::OnlyInB => Some((sub.stats.bytes_b, Category::MissingInA, name)),
+                SubdirStatus::Compared => None,
+            }
+        }));
+        // Largest first; ties keep reporting order.
+        files.sort_by_key(|f| std::cmp::Reverse(f.0));
+
+        for (size, category, path) in files.iter().take(self.n) {
+            writeln!(out, "{:>10}  {:<12}  {}", human_bytes(*size), category.as_str(), path.display())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+use std::io::{self, Write};
+
+use super::Renderer;
+use crate::report::Report;
+
+pub struct YamlRenderer;
+
+impl Renderer for YamlRenderer {
+    fn finish(&mut self, out: &mut dyn Write, report: &Report) -> io::Result<()> {
+        serde_yaml::to_writer(out, report).map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use serde::{Serialize, Serializer};
+
+use crate::compare::{ContentCheck, HashAlgo};
+
+/// The kind of difference a [`DiffEntry`] describes.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// Present in A but missing in B.
+    MissingInB,
+    /// Present in B but missing in A.
+    MissingInA,
+    /// Present in both, but as different kinds of entry (file, directory,
+    /// symlink, ...).
+    TypeConflict,
+    /// Present in both but with different content.
+    Changed,
+    /// Present in both with the same content but different metadata.
+    Metadata,
+    /// A symlink in A whose target does not exist.
+    DanglingInA,
+    /// A symlink in B whose target does not exist.
+    DanglingInB,
+    /// Present in both but could not be compared.
+    Errored,
+    /// Present in both and confirmed equal; only reported with `--show-same`.
+    Same,
+}
+
+impl Category {
+    /// Human-readable description, phrased for the given roots.
+    pub fn label(self, a: &str, b: &str) -> String {
+        match self {
+            Category::MissingInB => format!("Files present in {a} but MISSING in {b}"),
+            Category::MissingInA => format!("Files present in {b} but MISSING in {a}"),
+            Category::TypeConflict => "Paths present in BOTH but of a DIFFERENT TYPE".to_string(),
+            Category::Changed => "Files present in BOTH but with DIFFERENT CONTENT".to_string(),
+            Category::Metadata => "Files present in BOTH but with DIFFERENT METADATA".to_string(),
+            Category::DanglingInA => format!("Dangling symlinks in {a}"),
+            Category::DanglingInB => format!("Dangling symlinks in {b}"),
+            Category::Errored => "Files that could not be compared (errors)".to_string(),
+            Category::Same => "Files present in BOTH and IDENTICAL".to_string(),
+        }
+    }
+
+    /// Stable identifier used by the machine-readable formats.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::MissingInB => "missing-in-b",
+            Category::MissingInA => "missing-in-a",
+            Category::TypeConflict => "type-conflict",
+            Category::Changed => "changed",
+            Category::Metadata => "metadata",
+            Category::DanglingInA => "dangling-in-a",
+            Category::DanglingInB => "dangling-in-b",
+            Category::Errored => "errored",
+            Category::Same => "same",
+        }
+    }
+
+    /// All categories of difference, in the order they are reported.
+    pub const ALL: [Category; 8] = [
+        Category::MissingInB,
+        Category::MissingInA,
+        Category::TypeConflict,
+        Category::Changed,
+        Category::Metadata,
+        Category::DanglingInA,
+        Category::DanglingInB,
+        Category::Errored,
+    ];
+}
+
+/// A single reported difference, relative to its subdirectory.
+#[derive(Serialize, Clone, Debug)]
+pub struct DiffEntry {
+    pub category: Category,
+    #[serde(serialize_with = "lossy_path")]
+    pub path: PathBuf,
+    /// Size of the file under DIRECTORY_A, if it exists there.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_a: Option<u64>,
+    /// Size of the file under DIRECTORY_B, if it exists there.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_b: Option<u64>,
+    /// Hex digest of the A side, when its contents were hashed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest_a: Option<String>,
+    /// Hex digest of the B side, when its contents were hashed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest_b: Option<String>,
+    /// Modification time of the A side, if it was looked at.
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "opt_rfc3339")]
+    pub mtime_a: Option<SystemTime>,
+    /// Modification time of the B side, if it was looked at.
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "opt_rfc3339")]
+    pub mtime_b: Option<SystemTime>,
+    /// Set when the entry is a whole directory present on one side only:
+    /// the number of files below it, whose combined size is in `size_a`/`size_b`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<usize>,
+    /// Metadata attributes that differ between the two sides.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attrs: Vec<AttrDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Set when the contents were not compared because the file is over
+    /// the `--hash-max-size`; only size and mtime were.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub unchecked: bool,
+    /// For changed files, whether either side holds binary data rather
+    /// than text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary: Option<bool>,
+}
+
+/// One metadata attribute whose value differs between A and B; `None` when
+/// a side lacks the attribute.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct AttrDiff {
+    pub name: String,
+    pub a: Option<String>,
+    pub b: Option<String>,
+}
+
+impl AttrDiff {
+    pub fn new(name: impl Into<String>, a: Option<String>, b: Option<String>) -> Self {
+        AttrDiff { name: name.into(), a, b }
+    }
+}
+
+/// Which side of a changed file was modified more recently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Newer {
+    A,
+    B,
+    Same,
+}
+
+impl Newer {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Newer::A => "A newer",
+            Newer::B => "B newer",
+            Newer::Same => "same mtime",
+        }
+    }
+}
+
+impl DiffEntry {
+    /// An entry with no sizes, digests or error attached.
+    pub fn new(category: Category, path: PathBuf) -> Self {
+        DiffEntry {
+            category,
+            path,
+            size_a: None,
+            size_b: None,
+            digest_a: None,
+            digest_b: None,
+            mtime_a: None,
+            mtime_b: None,
+            files: None,
+            attrs: Vec::new(),
+            error: None,
+            unchecked: false,
+            binary: None,
+        }
+    }
+
+    /// The differing attribute called `name`, if any.
+    pub fn attr(&self, name: &str) -> Option<&AttrDiff> {
+        self.attrs.iter().find(|a| a.name == name)
+    }
+
+    /// Target of the symlink this entry is about, on whichever side has one.
+    pub fn target(&self) -> Option<&str> {
+        let attr = self.attr("target")?;
+        attr.a.as_deref().or(attr.b.as_deref())
+    }
+
+    /// Kinds of entry on both sides of a [`Category::TypeConflict`].
+    pub fn kinds(&self) -> (&str, &str) {
+        let attr = self.attr("type");
+        let a = attr.and_then(|t| t.a.as_deref()).unwrap_or("?");
+        let b = attr.and_then(|t| t.b.as_deref()).unwrap_or("?");
+        (a, b)
+    }
+
+    /// Comma-separated names of the differing metadata attributes.
+    pub fn attr_names(&self) -> String {
+        self.attrs.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", ")
+    }
+
+    /// `true` for a collapsed directory rather than a single file.
+    pub fn is_dir(&self) -> bool {
+        self.files.is_some()
+    }
+
+    /// Which side is newer, when both modification times are known; times
+    /// that `mtime` considers equal count as the same.
+    pub fn newer(&self, mtime: MtimeMatch) -> Option<Newer> {
+        let (a, b) = (self.mtime_a?, self.mtime_b?);
+        if mtime.matches(a, b) {
+            return Some(Newer::Same);
+        }
+        match a.cmp(&b) {
+            std::cmp::Ordering::Greater => Some(Newer::A),
+            std::cmp::Ordering::Less => Some(Newer::B),
+            std::cmp::Ordering::Equal => Some(Newer::Same),
+        }
+    }
+}
+
+/// Granularity at which modification times are compared.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MtimePrecision {
+    /// Nanoseconds, or whatever the filesystem records
+    #[default]
+    Ns,
+    /// Whole seconds; sub-second parts are ignored
+    S,
+}
+
+/// When two modification times count as equal.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MtimeMatch {
+    /// Largest difference that is still equal.
+    pub window: Duration,
+    pub precision: MtimePrecision,
+}
+
+impl MtimeMatch {
+    pub fn matches(self, a: SystemTime, b: SystemTime) -> bool {
+        let (a, b) = match self.precision {
+            MtimePrecision::Ns => (a, b),
+            MtimePrecision::S => (whole_seconds(a), whole_seconds(b)),
+        };
+        let apart = a.duration_since(b).or_else(|_| b.duration_since(a)).unwrap_or_default();
+        apart <= self.window
+    }
+}
+
+/// `time` rounded down to a whole second.
+fn whole_seconds(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => UNIX_EPOCH + Duration::from_secs(d.as_secs()),
+        Err(e) => {
+            let before = e.duration();
+            UNIX_EPOCH - Duration::from_secs(before.as_secs() + u64::from(before.subsec_nanos() > 0))
+        }
+    }
+}
+
+/// Outcome of comparing one direct subdirectory of the two roots.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SubdirStatus {
+    /// Present on both sides; see the entries for differences.
+    Compared,
+    /// Present only under DIRECTORY_A.
+    OnlyInA,
+    /// Present only under DIRECTORY_B.
+    OnlyInB,
+}
+
+/// Counters gathered while comparing.
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct Stats {
+    /// Files found under DIRECTORY_A: regular files, symlinks (not followed
+    /// unless `--follow-symlinks`) and special files.
+    pub files_a: usize,
+    /// Files found under DIRECTORY_B, counted the same way.
+    pub files_b: usize,
+    /// Total size of the files under DIRECTORY_A; a symlink counts with the
+    /// length of its target path.
+    pub bytes_a: u64,
+    /// Total size of the files under DIRECTORY_B, likewise.
+    pub bytes_b: u64,
+    /// Bytes read to compare contents, both sides combined.
+    pub bytes_hashed: u64,
+    /// Files compared by size and mtime only, for being over the
+    /// `--hash-max-size`.
+    pub unchecked: usize,
+    /// Time spent walking both trees, in seconds.
+    pub walk_secs: f64,
+    /// Time spent comparing file contents, in seconds.
+    pub hash_secs: f64,
+}
+
+impl std::ops::AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.files_a += other.files_a;
+        self.files_b += other.files_b;
+        self.bytes_a += other.bytes_a;
+        self.bytes_b += other.bytes_b;
+        self.bytes_hashed += other.bytes_hashed;
+        self.unchecked += other.unchecked;
+        self.walk_secs += other.walk_secs;
+        self.hash_secs += other.hash_secs;
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SubdirReport {
+    #[serde(serialize_with = "lossy_path")]
+    pub name: PathBuf,
+    pub status: SubdirStatus,
+    pub entries: Vec<DiffEntry>,
+    pub stats: Stats,
+    /// Files per category left out of `entries` by `--max-diffs`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub omitted: BTreeMap<Category, usize>,
+    /// Whether the comparison stopped early (`--stop-at-max`, `--fail-fast`).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stopped: bool,
+}
+
+impl SubdirStatus {
+    /// Stable identifier used by the machine-readable formats.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SubdirStatus::Compared => "compared",
+            SubdirStatus::OnlyInA => "only-in-a",
+            SubdirStatus::OnlyInB => "only-in-b",
+        }
+    }
+}
+
+impl SubdirReport {
+    /// A report with nothing omitted.
+    pub fn new(name: PathBuf, status: SubdirStatus, entries: Vec<DiffEntry>, stats: Stats) -> Self {
+        SubdirReport { name, status, entries, stats, omitted: BTreeMap::new(), stopped: false }
+    }
+
+    /// Entries of the given category, in reporting order.
+    pub fn entries_in(&self, category: Category) -> impl Iterator<Item = &DiffEntry> {
+        self.entries.iter().filter(move |e| e.category == category)
+    }
+
+    /// Number of files of the given category, counting every file below a
+    /// collapsed directory and those omitted by `--max-diffs`; the files of a
+    /// subdirectory present on one side only count as missing.
+    pub fn count(&self, category: Category) -> usize {
+        match (self.status, category) {
+            (SubdirStatus::OnlyInA, Category::MissingInB) => self.stats.files_a,
+            (SubdirStatus::OnlyInB, Category::MissingInA) => self.stats.files_b,
+            (SubdirStatus::OnlyInA | SubdirStatus::OnlyInB, _) => 0,
+            (SubdirStatus::Compared, _) => {
+                self.entries_in(category).map(|e| e.files.unwrap_or(1)).sum::<usize>() + self.omitted.get(&category).unwrap_or(&0)
+            }
+        }
+    }
+
+    /// Net bytes this subdirectory holds in A over B: files only in A minus
+    /// files only in B, plus the size difference of changed files.
+    pub fn size_delta(&self) -> i64 {
+        match self.status {
+            SubdirStatus::OnlyInA => self.stats.bytes_a as i64,
+            SubdirStatus::OnlyInB => -(self.stats.bytes_b as i64),
+            SubdirStatus::Compared => self
+                .entries
+                .iter()
+                .map(|e| match e.category {
+                    Category::MissingInB => e.size_a.unwrap_or(0) as i64,
+                    Category::MissingInA => -(e.size_b.unwrap_or(0) as i64),
+                    Category::Changed | Category::TypeConflict => e.size_a.unwrap_or(0) as i64 - e.size_b.unwrap_or(0) as i64,
+                    Category::Metadata | Category::DanglingInA | Category::DanglingInB | Category::Errored | Category::Same => 0,
+                })
+                .sum(),
+        }
+    }
+
+    /// Whether there is any difference of `category`, counting a subdirectory
+    /// present on one side only as missing files, and empty directories too.
+    pub fn differs_in(&self, category: Category) -> bool {
+        match self.status {
+            SubdirStatus::OnlyInA => category == Category::MissingInB,
+            SubdirStatus::OnlyInB => category == Category::MissingInA,
+            SubdirStatus::Compared => self.count(category) > 0 || self.entries_in(category).next().is_some(),
+        }
+    }
+
+    /// `true` when the subdirectory exists on both sides and nothing differs.
+    pub fn is_identical(&self) -> bool {
+        self.status == SubdirStatus::Compared
+            && self.entries.iter().all(|e| e.category == Category::Same)
+            && self.omitted.is_empty()
+    }
+}
+
+/// Full result of a comparison run, consumed by the renderers.
+#[derive(Serialize, Clone, Debug)]
+pub struct Report {
+    #[serde(serialize_with = "lossy_path")]
+    pub dir_a: PathBuf,
+    #[serde(serialize_with = "lossy_path")]
+    pub dir_b: PathBuf,
+    /// Whether file contents were compared.
+    pub hash: bool,
+    /// How contents were compared.
+    pub content: ContentCheck,
+    /// Digest used for the content comparison.
+    pub algo: HashAlgo,
+    /// When modification times count as equal.
+    #[serde(skip)]
+    pub mtime: MtimeMatch,
+    pub subdirs: Vec<SubdirReport>,
+    /// Wall-clock duration of the run, in seconds.
+    pub elapsed_secs: f64,
+}
+
+impl Report {
+    /// An empty report for comparing `dir_a` with `dir_b`.
+    pub fn new(dir_a: PathBuf, dir_b: PathBuf, hash: bool) -> Self {
+        Report { dir_a, dir_b, hash, content: ContentCheck::Hash, algo: HashAlgo::Sha256, mtime: MtimeMatch::default(), subdirs: Vec::new(), elapsed_secs: 0.0 }
+    }
+
+    /// A report for checking `dir` against an expected listing read from
+    /// `source` (a manifest or specification file), which plays the role of
+    /// DIRECTORY_A.
+    pub fn verification(source: &Path, dir: &Path, hash: bool, result: SubdirReport) -> Self {
+        Report { subdirs: vec![result], ..Report::new(source.to_path_buf(), dir.to_path_buf(), hash) }
+    }
+
+    /// Where `sub` is below each root; the root itself for the `.` subdirectory
+    /// of verification reports.
+    pub fn subdir_paths(&self, sub: &SubdirReport) -> (PathBuf, PathBuf) {
+        let name: PathBuf = sub.name.components().filter(|c| *c != Component::CurDir).collect();
+        let join = |root: &Path| if name.as_os_str().is_empty() { root.to_path_buf() } else { root.join(&name) };
+        (join(&self.dir_a), join(&self.dir_b))
+    }
+
+    /// Stats summed over all subdirectories.
+    pub fn stats(&self) -> Stats {
+        let mut total = Stats::default();
+        for sub in &self.subdirs {
+            total += sub.stats;
+        }
+        total
+    }
+
+    /// Whether any subdirectory has a difference of `category`.
+    pub fn differs_in(&self, category: Category) -> bool {
+        self.subdirs.iter().any(|s| s.differs_in(category))
+    }
+
+    /// How contents were compared, e.g. `sha256` or `byte-by-byte`.
+    pub fn method(&self) -> String {
+        match self.content {
+            ContentCheck::Hash => self.algo.as_str().to_string(),
+            ContentCheck::Bytes => "byte-by-byte".to_string(),
+            ContentCheck::Quick => format!("sampled {}", self.algo.as_str()),
+            ContentCheck::SizeOnly => "size only".to_string(),
+        }
+    }
+
+    /// Number of files of the given category across all subdirectories.
+    pub fn count(&self, category: Category) -> usize {
+        self.subdirs.iter().map(|s| s.count(category)).sum()
+    }
+}
+
+/// Serialize a path as a (lossily converted) UTF-8 string.
+pub fn lossy_path<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+/// Format a timestamp as RFC 3339 in UTC, e.g. `2024-05-01T12:34:56.789Z`.
+pub fn rfc3339(time: SystemTime) -> String {
+    let (secs, nanos) = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+        // Borrow a second for the fraction, which counts forward.
+        Err(e) => match e.duration().subsec_nanos() {
+            0 => (-(e.duration().as_secs() as i64), 0),
+            nanos => (-(e.duration().as_secs() as i64) - 1, 1_000_000_000 - nanos),
+        },
+    };
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let frac = if nanos == 0 { String::new() } else { format!(".{:09}", nanos).trim_end_matches('0').to_string() };
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}{frac}Z", rem / 3600, rem / 60 % 60, rem % 60)
+}
+
+fn opt_rfc3339<S: Serializer>(time: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(t) => serializer.serialize_str(&rfc3339(*t)),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_sided_subdirs_count_as_missing_files() {
+        let only_a = SubdirReport::new("x".into(), SubdirStatus::OnlyInA, Vec::new(), Stats { files_a: 3, ..Stats::default() });
+        let only_b = SubdirReport::new("y".into(), SubdirStatus::OnlyInB, Vec::new(), Stats { files_b: 2, ..Stats::default() });
+        let compared = SubdirReport::new(
+            "z".into(),
+            SubdirStatus::Compared,
+            vec![DiffEntry::new(Category::MissingInA, "f".into())],
+            Stats::default(),
+        );
+        let mut report = Report::new("a".into(), "b".into(), false);
+        report.subdirs = vec![only_a, only_b, compared];
+        assert_eq!(report.count(Category::MissingInB), 3);
+        assert_eq!(report.count(Category::MissingInA), 3);
+        assert_eq!(report.count(Category::Changed), 0);
+    }
+
+    #[test]
+    fn rfc3339_before_the_epoch() {
+        assert_eq!(rfc3339(UNIX_EPOCH - Duration::from_secs(1)), "1969-12-31T23:59:59Z");
+        assert_eq!(rfc3339(UNIX_EPOCH - Duration::from_secs(86_400)), "1969-12-31T00:00:00Z");
+        assert_eq!(rfc3339(UNIX_EPOCH - Duration::from_millis(1500)), "1969-12-31T23:59:58.5Z");
+        assert_eq!(rfc3339(UNIX_EPOCH + Duration::from_secs(86_400)), "1970-01-02T00:00:00Z");
+    }
+}